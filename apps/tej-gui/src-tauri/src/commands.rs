@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use serde::Serialize;
 use tauri::{AppHandle, Emitter, State};
-use tej_core::{ProgressUpdate, TestConfig, TestPhase};
+use tej_core::{Profile, ProgressUpdate, SpeedTestError, TestConfig, TestPhase};
 
 use crate::state::AppState;
 
@@ -18,9 +18,18 @@ struct ProgressEvent {
 pub async fn start_speed_test(
     app: AppHandle,
     state: State<'_, AppState>,
+    profile: Option<String>,
     connections: Option<usize>,
 ) -> Result<tej_core::SpeedTestResult, String> {
-    let mut config = TestConfig::default();
+    let profile = match profile {
+        Some(name) => name.parse::<Profile>()?,
+        None => Profile::default(),
+    };
+    // Show whatever phases succeeded rather than failing the whole test
+    let mut config = profile.apply(TestConfig {
+        continue_on_error: true,
+        ..TestConfig::default()
+    });
     if let Some(c) = connections {
         if c == 0 || c > 32 {
             return Err("connections must be between 1 and 32".to_string());
@@ -31,10 +40,12 @@ pub async fn start_speed_test(
     let app_handle = app.clone();
     let progress: tej_core::ProgressCallback = Arc::new(move |update: ProgressUpdate| {
         let phase = match update.phase {
+            TestPhase::Connection => "connection",
             TestPhase::Latency => "latency",
-            TestPhase::Download => "download",
-            TestPhase::Upload => "upload",
+            TestPhase::Download | TestPhase::SingleStreamDownload => "download",
+            TestPhase::Upload | TestPhase::SingleStreamUpload => "upload",
             TestPhase::PacketLoss => "packet_loss",
+            TestPhase::UdpPacketLoss => "udp_packet_loss",
             TestPhase::Done => "done",
         };
 
@@ -49,13 +60,41 @@ pub async fn start_speed_test(
         );
     });
 
-    let result = tej_core::run_speed_test(&config, Some(progress))
-        .await
-        .map_err(|e| e.to_string())?;
+    let cancel = state.begin_test()?;
+    let outcome = tej_core::run_speed_test_with_cancel(&config, Some(progress), cancel).await;
+    state.end_test();
+
+    match outcome {
+        Ok(result) => {
+            state.save_result(result.clone());
+            Ok(result)
+        }
+        // Stopped by the user: show what completed, but keep it out of history
+        Err(SpeedTestError::Cancelled(partial)) => Ok(*partial),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn stop_speed_test(state: State<'_, AppState>) -> bool {
+    state.cancel_test()
+}
 
-    state.save_result(result.clone());
+#[derive(Serialize)]
+pub struct ProfileInfo {
+    name: &'static str,
+    description: &'static str,
+}
 
-    Ok(result)
+#[tauri::command]
+pub fn list_profiles() -> Vec<ProfileInfo> {
+    Profile::ALL
+        .into_iter()
+        .map(|p| ProfileInfo {
+            name: p.name(),
+            description: p.description(),
+        })
+        .collect()
 }
 
 #[tauri::command]
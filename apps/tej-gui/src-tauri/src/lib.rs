@@ -12,11 +12,13 @@ pub fn run() {
                 .path()
                 .app_data_dir()
                 .unwrap_or_else(|_| std::path::PathBuf::from("."));
-            app.manage(AppState::new(data_dir));
+            app.manage(AppState::new(&data_dir));
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::start_speed_test,
+            commands::stop_speed_test,
+            commands::list_profiles,
             commands::get_history
         ])
         .run(tauri::generate_context!())
@@ -1,32 +1,59 @@
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::sync::Mutex;
 
-use tej_core::SpeedTestResult;
-
-const MAX_HISTORY: usize = 100;
+use tej_core::{CancellationToken, History, SpeedTestResult};
 
 pub struct AppState {
     pub history: Mutex<Vec<SpeedTestResult>>,
-    pub data_dir: PathBuf,
+    pub store: History,
+    /// Cancels the test currently in progress, if any
+    pub running: Mutex<Option<CancellationToken>>,
 }
 
 impl AppState {
-    pub fn new(data_dir: PathBuf) -> Self {
-        let history = Self::load_history(&data_dir).unwrap_or_default();
+    pub fn new(data_dir: &Path) -> Self {
+        let store = History::in_dir(data_dir);
+        let history = store.load().unwrap_or_else(|e| {
+            eprintln!("Failed to load history: {e}");
+            Vec::new()
+        });
         Self {
             history: Mutex::new(history),
-            data_dir,
+            store,
+            running: Mutex::new(None),
+        }
+    }
+
+    /// Registers a new running test and returns its cancellation token.
+    /// Fails if a test is already running.
+    pub fn begin_test(&self) -> Result<CancellationToken, String> {
+        let mut running = self.running.lock().map_err(|e| e.to_string())?;
+        if running.is_some() {
+            return Err("a speed test is already running".to_string());
         }
+        let token = CancellationToken::new();
+        *running = Some(token.clone());
+        Ok(token)
     }
 
-    fn history_path(data_dir: &Path) -> PathBuf {
-        data_dir.join("history.json")
+    pub fn end_test(&self) {
+        if let Ok(mut running) = self.running.lock() {
+            *running = None;
+        }
     }
 
-    fn load_history(data_dir: &Path) -> Option<Vec<SpeedTestResult>> {
-        let path = Self::history_path(data_dir);
-        let data = std::fs::read_to_string(path).ok()?;
-        serde_json::from_str(&data).ok()
+    /// Cancels the running test. Returns `false` if no test was running.
+    pub fn cancel_test(&self) -> bool {
+        match self.running.lock() {
+            Ok(running) => match running.as_ref() {
+                Some(token) => {
+                    token.cancel();
+                    true
+                }
+                None => false,
+            },
+            Err(_) => false,
+        }
     }
 
     pub fn get_history(&self) -> Vec<SpeedTestResult> {
@@ -41,25 +68,9 @@ impl AppState {
                 poisoned.into_inner()
             }
         };
-        history.push(result);
-        if history.len() > MAX_HISTORY {
-            let excess = history.len() - MAX_HISTORY;
-            history.drain(0..excess);
-        }
-        let path = Self::history_path(&self.data_dir);
-        match serde_json::to_string_pretty(&*history) {
-            Ok(json) => {
-                if let Err(e) = std::fs::create_dir_all(&self.data_dir) {
-                    eprintln!("Failed to create data dir: {e}");
-                    return;
-                }
-                if let Err(e) = std::fs::write(&path, json) {
-                    eprintln!("Failed to write history: {e}");
-                }
-            }
-            Err(e) => {
-                eprintln!("Failed to serialize history: {e}");
-            }
+        match self.store.append(&[result]) {
+            Ok(stored) => *history = stored,
+            Err(e) => eprintln!("Failed to write history: {e}"),
         }
     }
 }
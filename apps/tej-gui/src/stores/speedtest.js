@@ -1,4 +1,4 @@
-import { writable } from "svelte/store";
+import { get, writable } from "svelte/store";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 
@@ -9,12 +9,24 @@ export const latencyMs = writable(0);
 export const result = writable(null);
 export const error = writable(null);
 export const running = writable(false);
+export const stopping = writable(false);
 export const history = writable([]);
+export const profile = writable("standard");
+export const profiles = writable([]);
 
 let unlisten = null;
 
+export async function loadProfiles() {
+  try {
+    profiles.set(await invoke("list_profiles"));
+  } catch (e) {
+    error.set(e.toString());
+  }
+}
+
 export async function startTest() {
   running.set(true);
+  stopping.set(false);
   phase.set("starting");
   speedMbps.set(0);
   progress.set(0);
@@ -39,13 +51,17 @@ export async function startTest() {
   });
 
   try {
-    const res = await invoke("start_speed_test", { connections: 6 });
+    const res = await invoke("start_speed_test", { profile: get(profile) });
     result.set(res);
-    history.update((h) => [...h, res].slice(-100));
+    // A stopped test returns partial results, which are not kept in history
+    if (!get(stopping)) {
+      history.update((h) => [...h, res].slice(-100));
+    }
   } catch (e) {
     error.set(e.toString());
   } finally {
     running.set(false);
+    stopping.set(false);
     phase.set("idle");
     if (unlisten) {
       unlisten();
@@ -53,3 +69,12 @@ export async function startTest() {
     }
   }
 }
+
+export async function stopTest() {
+  stopping.set(true);
+  try {
+    await invoke("stop_speed_test");
+  } catch (e) {
+    error.set(e.toString());
+  }
+}
@@ -0,0 +1,188 @@
+use std::path::{Path, PathBuf};
+
+use clap::Subcommand;
+use tej_core::{Profile, TestConfig};
+use toml::Table;
+
+#[derive(Subcommand)]
+pub enum Action {
+    /// Print the configuration a test would use, after applying the config
+    /// file and profile, as TOML
+    Show,
+    /// Print the config file location
+    Path,
+}
+
+/// Default config file location: `tej/config.toml` under the user's config
+/// directory (`$XDG_CONFIG_HOME`, usually `~/.config`, on Linux).
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("tej").join("config.toml"))
+}
+
+/// Loads the test configuration from `path`, or from the default location
+/// when no path is given, then applies `profile` (or the file's `profile`
+/// key). A missing default file yields the built-in defaults; a missing
+/// explicit file is an error.
+pub fn load(path: Option<&Path>, profile: Option<&str>) -> Result<TestConfig, String> {
+    let (path, explicit) = match path {
+        Some(path) => (Some(path.to_path_buf()), true),
+        None => (default_path(), false),
+    };
+
+    let contents = match path {
+        Some(ref path) => match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => String::new(),
+            Err(e) => return Err(format!("Cannot read {}: {e}", path.display())),
+        },
+        None => String::new(),
+    };
+
+    parse(&contents, profile).map_err(|e| match path {
+        Some(path) => format!("{}: {e}", path.display()),
+        None => e,
+    })
+}
+
+pub fn run(action: &Action, path: Option<&Path>, profile: Option<&str>) -> Result<(), String> {
+    match action {
+        Action::Show => {
+            let config = load(path, profile)?;
+            let toml = toml::to_string(&config).map_err(|e| e.to_string())?;
+            print!("{toml}");
+        }
+        Action::Path => match path.map(Path::to_path_buf).or_else(default_path) {
+            Some(path) => println!("{}", path.display()),
+            None => return Err("Cannot determine the user config directory".to_string()),
+        },
+    }
+    Ok(())
+}
+
+/// Parses a config file. Top-level keys are `TestConfig` fields, plus:
+///
+/// - `profile`: profile used when `--profile` isn't given
+/// - `[profiles.<name>]`: user-defined profiles, each a table of `TestConfig`
+///   fields applied over the top-level values
+///
+/// The selected profile's values win over the top-level ones. A user profile
+/// named like a built-in one replaces it.
+fn parse(contents: &str, profile: Option<&str>) -> Result<TestConfig, String> {
+    let mut base: Table = contents.parse().map_err(|e| format!("{e}"))?;
+
+    let mut profiles = match base.remove("profiles") {
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(_) => return Err("`profiles` must be a table of profiles".to_string()),
+        None => Table::new(),
+    };
+    let file_profile = match base.remove("profile") {
+        Some(toml::Value::String(name)) => Some(name),
+        Some(_) => return Err("`profile` must be a profile name".to_string()),
+        None => None,
+    };
+
+    let Some(name) = profile.map(str::to_string).or(file_profile) else {
+        return to_config(base);
+    };
+
+    match profiles.remove(&name) {
+        Some(toml::Value::Table(overrides)) => {
+            base.extend(overrides);
+            to_config(base).map_err(|e| format!("profile `{name}`: {e}"))
+        }
+        Some(_) => Err(format!("profile `{name}` must be a table")),
+        None => match name.parse::<Profile>() {
+            Ok(builtin) => Ok(builtin.apply(to_config(base)?)),
+            Err(_) => Err(unknown_profile(&name, &profiles)),
+        },
+    }
+}
+
+fn to_config(table: Table) -> Result<TestConfig, String> {
+    toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| e.to_string())
+}
+
+fn unknown_profile(name: &str, user_profiles: &Table) -> String {
+    let mut available: Vec<&str> = Profile::ALL.iter().map(|p| p.name()).collect();
+    available.extend(user_profiles.keys().map(String::as_str));
+    format!(
+        "Unknown profile `{name}` (available: {})",
+        available.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_partial_file_keeps_defaults() {
+        let config = parse(
+            r#"
+            download_url = "http://10.0.0.5:8080/__down"
+            parallel_connections = 8
+            timeout = 10
+            packet_loss_timeout = 0.5
+            "#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(config.download_url, "http://10.0.0.5:8080/__down");
+        assert_eq!(config.parallel_connections, 8);
+        assert_eq!(config.timeout, Duration::from_secs(10));
+        assert_eq!(config.packet_loss_timeout, Duration::from_millis(500));
+        assert_eq!(config.upload_url, TestConfig::default().upload_url);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_keys() {
+        assert!(parse("paralel_connections = 8", None).is_err());
+    }
+
+    #[test]
+    fn test_missing_explicit_file_is_an_error() {
+        assert!(load(Some(Path::new("/nonexistent/tej.toml")), None).is_err());
+    }
+
+    #[test]
+    fn test_builtin_profile_over_file_values() {
+        let config = parse(
+            r#"
+            download_url = "http://10.0.0.5:8080/__down"
+            latency_samples = 30
+            "#,
+            Some("quick"),
+        )
+        .unwrap();
+        assert_eq!(config.download_url, "http://10.0.0.5:8080/__down");
+        assert_eq!(config.latency_samples, 8);
+    }
+
+    #[test]
+    fn test_user_profile_selected_by_file() {
+        let config = parse(
+            r#"
+            profile = "line-check"
+            parallel_connections = 2
+
+            [profiles.line-check]
+            latency_samples = 40
+            test_duration = 30
+            "#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(config.parallel_connections, 2);
+        assert_eq!(config.latency_samples, 40);
+        assert_eq!(config.test_duration, Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_unknown_profile_lists_available() {
+        let err = parse("[profiles.mine]\nupload_size = 1", Some("turbo")).unwrap_err();
+        assert!(err.contains("quick, standard, thorough, mine"));
+    }
+}
@@ -1,5 +1,5 @@
 use comfy_table::{Cell, Color, Table};
-use tej_core::SpeedTestResult;
+use tej_core::{history, PacketLossResult, SpeedTestResult, TestPhase, ThroughputResult};
 
 pub fn print_results(result: &SpeedTestResult) {
     println!();
@@ -14,32 +14,306 @@ pub fn print_results(result: &SpeedTestResult) {
         table.add_row(vec!["Server", loc]);
     }
 
+    if let (Some(ip), Some(family)) = (result.remote_ip, result.address_family) {
+        table.add_row(vec![
+            "Server IP".to_string(),
+            format!("{} ({})", ip, family.as_str()),
+        ]);
+    }
+
+    match (&result.interface, result.source_address) {
+        (Some(interface), Some(source)) => {
+            table.add_row(vec![
+                "Interface".to_string(),
+                format!("{interface} ({source})"),
+            ]);
+        }
+        (Some(interface), None) => {
+            table.add_row(vec!["Interface".to_string(), interface.clone()]);
+        }
+        (None, Some(source)) => {
+            table.add_row(vec!["Source IP".to_string(), source.to_string()]);
+        }
+        (None, None) => {}
+    }
+
+    if let Some(ref conn) = result.connection {
+        table.add_row(vec![
+            "DNS lookup".to_string(),
+            format!("{:.1} ms", conn.dns_ms),
+        ]);
+        table.add_row(vec![
+            "TCP connect".to_string(),
+            format!("{:.1} ms", conn.tcp_connect_ms),
+        ]);
+        if let Some(tls_ms) = conn.tls_handshake_ms {
+            table.add_row(vec![
+                "TLS handshake".to_string(),
+                format!("{:.1} ms", tls_ms),
+            ]);
+        }
+        table.add_row(vec![
+            "Time to first byte".to_string(),
+            format!("{:.1} ms", conn.ttfb_ms),
+        ]);
+    } else if let Some(err) = result.phase_error(TestPhase::Connection) {
+        add_failed_row(&mut table, "Connection", err);
+    }
+
     if let Some(ref latency) = result.latency {
         table.add_row(vec![
             "Latency (avg)".to_string(),
             format!("{:.1} ms", latency.avg_ms),
         ]);
+        table.add_row(vec![
+            "Latency (median)".to_string(),
+            format!("{:.1} ms", latency.median_ms),
+        ]);
         table.add_row(vec![
             "Latency (min/max)".to_string(),
             format!("{:.1} / {:.1} ms", latency.min_ms, latency.max_ms),
         ]);
+        table.add_row(vec![
+            "Latency (p90/p95/p99)".to_string(),
+            format!(
+                "{:.1} / {:.1} / {:.1} ms",
+                latency.p90_ms, latency.p95_ms, latency.p99_ms
+            ),
+        ]);
+        table.add_row(vec![
+            "Latency (std dev)".to_string(),
+            format!(
+                "{:.1} ms ({} outliers)",
+                latency.stddev_ms, latency.outliers
+            ),
+        ]);
         table.add_row(vec![
             "Jitter".to_string(),
             format!("{:.1} ms", latency.jitter_ms),
         ]);
+    } else if let Some(err) = result.phase_error(TestPhase::Latency) {
+        add_failed_row(&mut table, "Latency", err);
     }
 
     if let Some(ref dl) = result.download {
-        table.add_row(vec!["Download".to_string(), format!("{:.2} Mbps", dl.mbps)]);
+        table.add_row(vec!["Download".to_string(), format_throughput(dl)]);
+        if let Some(streams) = format_connections(dl) {
+            table.add_row(vec!["Download streams".to_string(), streams]);
+        }
+        if let Some(ref single) = result.single_stream_download {
+            table.add_row(vec![
+                "Download (1 stream)".to_string(),
+                format_single_stream(dl, single),
+            ]);
+        }
+        if let Some(ref loaded) = dl.loaded_latency {
+            table.add_row(vec![
+                "Latency (download)".to_string(),
+                format!(
+                    "{:.1} ms (jitter {:.1} ms)",
+                    loaded.avg_ms, loaded.jitter_ms
+                ),
+            ]);
+        }
+    } else if let Some(err) = result.phase_error(TestPhase::Download) {
+        add_failed_row(&mut table, "Download", err);
+    }
+
+    if let Some(err) = result.phase_error(TestPhase::SingleStreamDownload) {
+        add_failed_row(&mut table, "Download (1 stream)", err);
     }
 
     if let Some(ref ul) = result.upload {
-        table.add_row(vec!["Upload".to_string(), format!("{:.2} Mbps", ul.mbps)]);
+        table.add_row(vec!["Upload".to_string(), format_throughput(ul)]);
+        if let Some(streams) = format_connections(ul) {
+            table.add_row(vec!["Upload streams".to_string(), streams]);
+        }
+        if let Some(ref single) = result.single_stream_upload {
+            table.add_row(vec![
+                "Upload (1 stream)".to_string(),
+                format_single_stream(ul, single),
+            ]);
+        }
+        if let Some(ref loaded) = ul.loaded_latency {
+            table.add_row(vec![
+                "Latency (upload)".to_string(),
+                format!(
+                    "{:.1} ms (jitter {:.1} ms)",
+                    loaded.avg_ms, loaded.jitter_ms
+                ),
+            ]);
+        }
+    } else if let Some(err) = result.phase_error(TestPhase::Upload) {
+        add_failed_row(&mut table, "Upload", err);
+    }
+
+    if let Some(err) = result.phase_error(TestPhase::SingleStreamUpload) {
+        add_failed_row(&mut table, "Upload (1 stream)", err);
+    }
+
+    if let Some(grade) = result.bufferbloat {
+        let increase = result.loaded_latency_increase_ms().unwrap_or(0.0);
+        table.add_row(vec![
+            "Bufferbloat".to_string(),
+            format!("{} (+{:.1} ms)", grade.as_str(), increase),
+        ]);
+    }
+
+    if let Some(ref loss) = result.packet_loss {
+        table.add_row(vec!["Packet Loss".to_string(), format_packet_loss(loss)]);
+    } else if let Some(err) = result.phase_error(TestPhase::PacketLoss) {
+        add_failed_row(&mut table, "Packet Loss", err);
+    }
+
+    if let Some(ref udp) = result.udp_packet_loss {
+        table.add_row(vec![
+            "Packet Loss (UDP)".to_string(),
+            format!(
+                "{} ({} out of order, {} duplicated)",
+                format_packet_loss(udp),
+                udp.out_of_order,
+                udp.duplicates
+            ),
+        ]);
+    } else if let Some(err) = result.phase_error(TestPhase::UdpPacketLoss) {
+        add_failed_row(&mut table, "Packet Loss (UDP)", err);
     }
 
-    if let Some(loss) = result.packet_loss {
-        table.add_row(vec!["Packet Loss".to_string(), format!("{:.1}%", loss)]);
+    println!("{table}");
+}
+
+/// Side-by-side summary of runs over different address families.
+pub fn print_comparison(results: &[SpeedTestResult]) {
+    let mut table = Table::new();
+    let mut header = vec![Cell::new("Metric").fg(Color::Cyan)];
+    header.extend(
+        results
+            .iter()
+            .map(|r| Cell::new(r.address_family.map_or("Unknown", |f| f.as_str())).fg(Color::Cyan)),
+    );
+    table.set_header(header);
+
+    let row = |metric: &str, value: &dyn Fn(&SpeedTestResult) -> Option<String>| {
+        let mut cells = vec![metric.to_string()];
+        cells.extend(
+            results
+                .iter()
+                .map(|r| value(r).unwrap_or_else(|| "-".to_string())),
+        );
+        cells
+    };
+    table.add_row(row("Latency (avg)", &|r| {
+        r.latency.as_ref().map(|l| format!("{:.1} ms", l.avg_ms))
+    }));
+    table.add_row(row("Download", &|r| {
+        r.download.as_ref().map(|d| format!("{:.2} Mbps", d.mbps))
+    }));
+    table.add_row(row("Upload", &|r| {
+        r.upload.as_ref().map(|u| format!("{:.2} Mbps", u.mbps))
+    }));
+    table.add_row(row("Packet Loss", &|r| {
+        r.packet_loss
+            .as_ref()
+            .map(|p| format!("{:.1}%", p.loss_percent))
+    }));
+    table.add_row(row("Failed phases", &|r| Some(r.errors.len().to_string())));
+
+    println!();
+    println!("{table}");
+}
+
+/// One row per saved result, oldest first.
+pub fn print_history(results: &[SpeedTestResult]) {
+    let mut table = Table::new();
+    table.set_header(
+        [
+            "ID", "Time", "Server", "Latency", "Download", "Upload", "Loss",
+        ]
+        .map(|h| Cell::new(h).fg(Color::Cyan)),
+    );
+
+    let dash = || "-".to_string();
+    for r in results {
+        table.add_row(vec![
+            history::result_id(r),
+            r.timestamp
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            r.server_location.clone().unwrap_or_else(dash),
+            r.latency
+                .as_ref()
+                .map_or_else(dash, |l| format!("{:.1} ms", l.avg_ms)),
+            r.download
+                .as_ref()
+                .map_or_else(dash, |d| format!("{:.2} Mbps", d.mbps)),
+            r.upload
+                .as_ref()
+                .map_or_else(dash, |u| format!("{:.2} Mbps", u.mbps)),
+            r.packet_loss
+                .as_ref()
+                .map_or_else(dash, |p| format!("{:.1}%", p.loss_percent)),
+        ]);
     }
 
     println!("{table}");
 }
+
+fn add_failed_row(table: &mut Table, metric: &str, error: &str) {
+    table.add_row(vec![
+        Cell::new(metric),
+        Cell::new(format!("FAILED: {error}")).fg(Color::Red),
+    ]);
+}
+
+fn format_throughput(result: &ThroughputResult) -> String {
+    // Show the whole-transfer figure too when ramp-up was excluded
+    if (result.mbps - result.raw_mbps).abs() >= 0.01 {
+        format!("{:.2} Mbps (raw {:.2})", result.mbps, result.raw_mbps)
+    } else {
+        format!("{:.2} Mbps", result.mbps)
+    }
+}
+
+fn format_connections(result: &ThroughputResult) -> Option<String> {
+    let (min, max) = result.connection_speed_range()?;
+    let fairness = result.fairness?;
+    let mut text = format!(
+        "{} x {:.2}-{:.2} Mbps (fairness {:.2})",
+        result.connections.len(),
+        min,
+        max,
+        fairness
+    );
+    if let Some(n) = result.saturating_connections {
+        text.push_str(&format!(", saturated at {n}"));
+    }
+    let failed = result
+        .connections
+        .iter()
+        .filter(|c| c.error.is_some())
+        .count();
+    if failed > 0 {
+        text.push_str(&format!(", {failed} failed"));
+    }
+    Some(text)
+}
+
+fn format_single_stream(parallel: &ThroughputResult, single: &ThroughputResult) -> String {
+    match parallel.speedup_over(single) {
+        Some(speedup) => format!("{:.2} Mbps (parallel {:.1}x faster)", single.mbps, speedup),
+        None => format!("{:.2} Mbps", single.mbps),
+    }
+}
+
+fn format_packet_loss(loss: &PacketLossResult) -> String {
+    let mut text = format!(
+        "{:.1}% ({}/{} received",
+        loss.loss_percent, loss.received, loss.sent
+    );
+    if let Some(rtt) = loss.avg_rtt_ms() {
+        text.push_str(&format!(", {:.1} ms avg RTT", rtt));
+    }
+    text.push(')');
+    text
+}
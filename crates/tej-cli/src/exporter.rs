@@ -0,0 +1,310 @@
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State as AxumState;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use chrono::{DateTime, Utc};
+use tej_core::{CancellationToken, SpeedTestResult, TestConfig};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::history;
+use crate::monitor::Schedule;
+
+/// Content type of the Prometheus text exposition format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// When the exporter runs tests.
+#[derive(Debug, Clone, Copy)]
+pub enum Mode {
+    /// In the background every `interval`, plus a random delay up to `jitter`
+    Scheduled {
+        interval: Duration,
+        jitter: Duration,
+    },
+    /// When scraped, unless the last test is younger than `max_age`
+    OnScrape { max_age: Duration },
+}
+
+pub struct ExporterOptions {
+    pub listen: SocketAddr,
+    pub mode: Mode,
+    pub save_history: bool,
+}
+
+struct Exporter {
+    config: TestConfig,
+    mode: Mode,
+    save_history: bool,
+    state: Mutex<State>,
+}
+
+/// Serves `/metrics` on `options.listen` until the process exits.
+pub async fn run(config: TestConfig, options: ExporterOptions) -> std::io::Result<()> {
+    let listener = TcpListener::bind(options.listen).await?;
+    let exporter = Arc::new(Exporter {
+        config,
+        mode: options.mode,
+        save_history: options.save_history,
+        state: Mutex::new(State::default()),
+    });
+
+    println!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+    if let Mode::Scheduled { interval, jitter } = options.mode {
+        tokio::spawn(run_scheduled(exporter.clone(), interval, jitter));
+    }
+
+    let app = Router::new()
+        .route("/", get(index))
+        .route("/metrics", get(metrics))
+        .with_state(exporter);
+    axum::serve(listener, app).await
+}
+
+async fn run_scheduled(exporter: Arc<Exporter>, interval: Duration, jitter: Duration) {
+    let mut schedule = Schedule::new(Instant::now(), interval);
+    loop {
+        let offset = jitter.mul_f64(rand::random::<f64>());
+        tokio::time::sleep_until(schedule.next(Instant::now(), offset)).await;
+        // Tests run outside the lock so scrapes are answered meanwhile
+        let outcome = exporter.test().await;
+        exporter.state.lock().await.record(outcome);
+    }
+}
+
+impl Exporter {
+    async fn test(&self) -> tej_core::Result<Vec<SpeedTestResult>> {
+        let outcome =
+            tej_core::run_speed_tests_with_cancel(&self.config, None, CancellationToken::new())
+                .await;
+        match outcome {
+            Ok(ref results) if self.save_history => {
+                if let Err(e) = history::save(results) {
+                    eprintln!("Warning: failed to save history: {e}");
+                }
+            }
+            Ok(_) => {}
+            Err(ref e) => eprintln!("Test failed: {e}"),
+        }
+        outcome
+    }
+}
+
+async fn index() -> impl IntoResponse {
+    "Tej exporter: metrics are at /metrics\n"
+}
+
+async fn metrics(AxumState(exporter): AxumState<Arc<Exporter>>) -> impl IntoResponse {
+    // Holding the lock across the test makes concurrent scrapes wait for
+    // one shared run instead of starting their own
+    let mut state = exporter.state.lock().await;
+    if let Mode::OnScrape { max_age } = exporter.mode {
+        if state.last_attempt.is_none_or(|at| at.elapsed() >= max_age) {
+            let outcome = exporter.test().await;
+            state.record(outcome);
+        }
+    }
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], state.render())
+}
+
+/// Results of the last successful test, kept until the next success so a
+/// failing line shows up as a stale `tej_last_success_timestamp_seconds`
+/// rather than missing series.
+#[derive(Default)]
+struct State {
+    results: Vec<SpeedTestResult>,
+    succeeded_at: Option<DateTime<Utc>>,
+    last_attempt: Option<Instant>,
+    tests: u64,
+    failures: u64,
+}
+
+impl State {
+    fn record(&mut self, outcome: tej_core::Result<Vec<SpeedTestResult>>) {
+        self.tests += 1;
+        self.last_attempt = Some(Instant::now());
+        match outcome {
+            Ok(results) => {
+                self.results = results;
+                self.succeeded_at = Some(Utc::now());
+            }
+            Err(_) => self.failures += 1,
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let gauge = |out: &mut String, name, help, value: fn(&SpeedTestResult) -> Option<f64>| {
+            let samples = self
+                .results
+                .iter()
+                .filter_map(|r| Some((labels(r), value(r)?)));
+            write_metric(out, name, "gauge", help, samples);
+        };
+
+        gauge(
+            &mut out,
+            "tej_download_bits_per_second",
+            "Steady-state download throughput.",
+            |r| r.download.as_ref().map(|d| d.bps),
+        );
+        gauge(
+            &mut out,
+            "tej_upload_bits_per_second",
+            "Steady-state upload throughput.",
+            |r| r.upload.as_ref().map(|u| u.bps),
+        );
+        gauge(
+            &mut out,
+            "tej_latency_min_seconds",
+            "Minimum idle round-trip time.",
+            |r| r.latency.as_ref().map(|l| l.min_ms / 1000.0),
+        );
+        gauge(
+            &mut out,
+            "tej_latency_avg_seconds",
+            "Average idle round-trip time.",
+            |r| r.latency.as_ref().map(|l| l.avg_ms / 1000.0),
+        );
+        gauge(
+            &mut out,
+            "tej_latency_max_seconds",
+            "Maximum idle round-trip time.",
+            |r| r.latency.as_ref().map(|l| l.max_ms / 1000.0),
+        );
+        gauge(
+            &mut out,
+            "tej_jitter_seconds",
+            "Idle latency jitter.",
+            |r| r.latency.as_ref().map(|l| l.jitter_ms / 1000.0),
+        );
+        gauge(
+            &mut out,
+            "tej_packet_loss_ratio",
+            "Share of HTTP probes that got no answer (0-1).",
+            |r| r.packet_loss.as_ref().map(|p| p.loss_percent / 100.0),
+        );
+        gauge(
+            &mut out,
+            "tej_udp_packet_loss_ratio",
+            "Share of UDP probes that got no answer (0-1).",
+            |r| r.udp_packet_loss.as_ref().map(|p| p.loss_percent / 100.0),
+        );
+
+        if let Some(at) = self.succeeded_at {
+            let at = at.timestamp_millis() as f64 / 1000.0;
+            write_metric(
+                &mut out,
+                "tej_last_success_timestamp_seconds",
+                "gauge",
+                "Unix time the last successful test finished.",
+                self.results.iter().map(|r| (labels(r), at)),
+            );
+        }
+        write_metric(
+            &mut out,
+            "tej_tests_total",
+            "counter",
+            "Tests run since the exporter started.",
+            [(String::new(), self.tests as f64)].into_iter(),
+        );
+        write_metric(
+            &mut out,
+            "tej_test_failures_total",
+            "counter",
+            "Tests that failed since the exporter started.",
+            [(String::new(), self.failures as f64)].into_iter(),
+        );
+        out
+    }
+}
+
+/// Writes one metric family. Families without samples are left out.
+fn write_metric(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    samples: impl Iterator<Item = (String, f64)>,
+) {
+    let mut samples = samples.peekable();
+    if samples.peek().is_none() {
+        return;
+    }
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{name}{labels} {value}");
+    }
+}
+
+fn labels(result: &SpeedTestResult) -> String {
+    format!(
+        "{{server_location=\"{}\",address_family=\"{}\"}}",
+        escape(result.server_location.as_deref().unwrap_or_default()),
+        result.address_family.map_or("", |f| f.as_str())
+    )
+}
+
+/// Escapes a label value for the text exposition format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tej_core::{AddressFamily, SpeedTestError, ThroughputResult};
+
+    #[test]
+    fn test_render_successful_test() {
+        let mut result = SpeedTestResult::new();
+        result.server_location = Some("SFO".to_string());
+        result.address_family = Some(AddressFamily::Ipv4);
+        result.download = Some(ThroughputResult::new(12_500_000, 1.0));
+
+        let mut state = State::default();
+        state.record(Ok(vec![result]));
+        let text = state.render();
+
+        assert!(text.contains("# TYPE tej_download_bits_per_second gauge\n"));
+        assert!(text.contains(
+            "tej_download_bits_per_second{server_location=\"SFO\",address_family=\"IPv4\"} 100000000\n"
+        ));
+        assert!(text.contains("tej_last_success_timestamp_seconds{server_location=\"SFO\""));
+        assert!(text.contains("tej_tests_total 1\n"));
+        // Upload wasn't measured, so the family is left out entirely
+        assert!(!text.contains("tej_upload_bits_per_second"));
+    }
+
+    #[test]
+    fn test_failure_keeps_last_success() {
+        let mut state = State::default();
+        let mut result = SpeedTestResult::new();
+        result.download = Some(ThroughputResult::new(1_000_000, 1.0));
+        state.record(Ok(vec![result]));
+        state.record(Err(SpeedTestError::Other("offline".to_string())));
+
+        let text = state.render();
+        assert!(text.contains("tej_download_bits_per_second{"));
+        assert!(text.contains("tej_tests_total 2\n"));
+        assert!(text.contains("tej_test_failures_total 1\n"));
+    }
+
+    #[test]
+    fn test_escape_label_value() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
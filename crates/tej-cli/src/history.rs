@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use clap::Subcommand;
+use tej_core::{history, History, SpeedTestResult};
+
+use crate::display;
+use crate::output::Output;
+
+#[derive(Subcommand)]
+pub enum Action {
+    /// List saved results, oldest first
+    List,
+    /// Show one saved result in full
+    Show {
+        /// Result ID from `tej history list`, or `latest`
+        id: String,
+    },
+    /// Delete all saved results
+    Clear,
+}
+
+/// History location: `tej/history.json` under the user's data directory
+/// (`$XDG_DATA_HOME`, usually `~/.local/share`, on Linux).
+fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("tej").join("history.json"))
+}
+
+fn store() -> Result<History, String> {
+    default_path()
+        .map(History::new)
+        .ok_or_else(|| "Cannot determine the user data directory".to_string())
+}
+
+/// Appends finished test results to the local history.
+pub fn save(results: &[SpeedTestResult]) -> Result<(), String> {
+    store()?.append(results).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn run(action: &Action, out: Output) -> Result<(), String> {
+    let store = store()?;
+    match action {
+        Action::List => {
+            let results = store.load().map_err(|e| e.to_string())?;
+            if !out.is_text() {
+                out.print_list(&results);
+            } else if results.is_empty() {
+                println!("No saved results in {}", store.path().display());
+            } else {
+                display::print_history(&results);
+            }
+        }
+        Action::Show { id } => {
+            let results = store.load().map_err(|e| e.to_string())?;
+            let result = history::find(&results, id)
+                .ok_or_else(|| format!("No saved result with ID {id}"))?;
+            out.print(std::slice::from_ref(result));
+        }
+        Action::Clear => match store.clear().map_err(|e| e.to_string())? {
+            Some(count) => println!("Deleted {count} saved results"),
+            None => println!("Deleted unreadable history file"),
+        },
+    }
+    Ok(())
+}
@@ -1,25 +1,182 @@
+mod config_file;
 mod display;
+mod exporter;
+mod history;
+mod monitor;
 mod output;
+mod phase;
 
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
-use tej_core::{ProgressUpdate, TestConfig, TestPhase};
+use tej_core::{
+    CancellationToken, IpVersion, ProgressCallback, ProgressUpdate, SpeedTestError,
+    SpeedTestResult, TestConfig, TestPhase,
+};
+use tej_server::{ServerConfig, DEFAULT_MAX_DOWNLOAD_BYTES};
+
+use crate::output::{Format, Output};
+use crate::phase::Phase;
 
 #[derive(Parser)]
 #[command(
     name = "tej",
-    about = "Tej - Honest internet speed test using Cloudflare CDN"
+    about = "Tej - Honest internet speed test using Cloudflare CDN",
+    args_conflicts_with_subcommands = true
 )]
-struct Args {
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Options for `tej run`, which is what `tej` does without a subcommand
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// Config file (defaults to tej/config.toml in the user config directory).
+    /// Command-line flags override its values.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Test profile: quick, standard, thorough, or one defined in the config file
+    #[arg(short = 'p', long, global = true)]
+    profile: Option<String>,
+
     /// Output format
-    #[arg(long, default_value = "text", value_parser = ["text", "json"])]
-    format: String,
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Leave out the CSV header row, e.g. when appending to an existing file
+    #[arg(long, global = true)]
+    no_header: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the full test: latency, download, upload and packet loss (default)
+    Run(RunArgs),
+    /// Run the full test on a schedule, saving each result to history
+    Monitor(MonitorArgs),
+    /// Serve Prometheus metrics from scheduled or on-scrape tests
+    Exporter(ExporterArgs),
+    /// Measure idle latency and jitter only
+    Latency(LatencyArgs),
+    /// Measure download throughput only
+    Download(TransferArgs),
+    /// Measure upload throughput only
+    Upload(TransferArgs),
+    /// Measure packet loss only (HTTP probes, plus UDP with --udp-echo)
+    Loss(LossArgs),
+    /// Show or clear results saved by previous runs
+    History {
+        #[command(subcommand)]
+        action: history::Action,
+    },
+    /// Show the effective configuration or the config file location
+    Config {
+        #[command(subcommand)]
+        action: config_file::Action,
+    },
+    /// Run a self-hosted tej server
+    Server(ServerArgs),
+}
+
+/// Which server to test against and how to reach it.
+#[derive(Args)]
+struct TargetArgs {
+    /// Base URL of a self-hosted tej-server (defaults to Cloudflare)
+    #[arg(long)]
+    server: Option<String>,
+
+    /// Address family: auto, 4, 6, or both to compare IPv4 and IPv6 back-to-back
+    #[arg(long, value_parser = ["auto", "4", "6", "both"])]
+    ip: Option<String>,
+
+    /// Network interface to run the test over, e.g. eth0 or wlan0 (Linux only)
+    #[arg(long)]
+    interface: Option<String>,
+
+    /// Local IP address to send test traffic from
+    #[arg(long)]
+    source: Option<IpAddr>,
+}
+
+impl TargetArgs {
+    fn apply(&self, mut config: TestConfig) -> TestConfig {
+        if let Some(ref url) = self.server {
+            config = config.with_server(url);
+        }
+        if let Some(ref ip) = self.ip {
+            config.ip_version = match ip.as_str() {
+                "4" => IpVersion::V4,
+                "6" => IpVersion::V6,
+                "both" => IpVersion::Both,
+                _ => IpVersion::Any,
+            };
+        }
+        if let Some(ref interface) = self.interface {
+            config.interface = Some(interface.clone());
+        }
+        if let Some(source) = self.source {
+            config.source_address = Some(source);
+        }
+        config
+    }
+}
+
+/// Connection count and duration of a download or upload phase.
+#[derive(Args)]
+struct TransferArgs {
+    #[command(flatten)]
+    target: TargetArgs,
 
     /// Number of parallel connections (1-32)
-    #[arg(short = 'c', long, default_value_t = 6, value_parser = clap::value_parser!(u64).range(1..=32))]
-    connections: u64,
+    #[arg(short = 'c', long, value_parser = clap::value_parser!(u64).range(1..=32))]
+    connections: Option<u64>,
+
+    /// Start with one connection and add more until throughput stops improving,
+    /// up to --connections
+    #[arg(long)]
+    adaptive: bool,
+
+    /// Run download/upload for a fixed number of seconds instead of a fixed size
+    #[arg(short = 'd', long, value_parser = clap::value_parser!(u64).range(2..=120))]
+    duration: Option<u64>,
+}
+
+impl TransferArgs {
+    fn apply(&self, mut config: TestConfig) -> TestConfig {
+        config = self.target.apply(config);
+        if let Some(connections) = self.connections {
+            config.parallel_connections = connections as usize;
+        }
+        if let Some(duration) = self.duration {
+            config.test_duration = Some(Duration::from_secs(duration));
+        }
+        config.adaptive_connections |= self.adaptive;
+        config
+    }
+}
+
+#[derive(Args)]
+struct RunArgs {
+    #[command(flatten)]
+    transfer: TransferArgs,
+
+    /// UDP echo server (host:port) for measuring real packet loss
+    #[arg(long)]
+    udp_echo: Option<String>,
+
+    /// Also run download and upload over a single connection for comparison
+    #[arg(long)]
+    compare_streams: bool,
+
+    /// Keep running the remaining phases when one fails, reporting the failure
+    #[arg(long)]
+    continue_on_error: bool,
 
     /// Skip download test
     #[arg(long)]
@@ -28,90 +185,448 @@ struct Args {
     /// Skip upload test
     #[arg(long)]
     no_upload: bool,
+
+    /// Don't save the results to the local history
+    #[arg(long)]
+    no_history: bool,
+}
+
+impl RunArgs {
+    fn apply(&self, mut config: TestConfig) -> TestConfig {
+        config = self.transfer.apply(config);
+        if let Some(ref addr) = self.udp_echo {
+            config.udp_echo_addr = Some(addr.clone());
+        }
+        config.compare_single_stream |= self.compare_streams;
+        config.continue_on_error |= self.continue_on_error;
+        config.skip_download |= self.no_download;
+        config.skip_upload |= self.no_upload;
+        config
+    }
+}
+
+#[derive(Args)]
+struct MonitorArgs {
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// Time between runs, e.g. 90s, 15m or 1h
+    #[arg(long, default_value = "15m", value_parser = monitor::parse_interval)]
+    interval: Duration,
+
+    /// Longest random delay added to each run so machines started together
+    /// don't test at the same moment (defaults to a tenth of the interval)
+    #[arg(long, value_parser = monitor::parse_interval)]
+    jitter: Option<Duration>,
+
+    /// Stop after this many runs instead of running until Ctrl-C
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    count: Option<u64>,
+}
+
+#[derive(Args)]
+struct ExporterArgs {
+    #[command(flatten)]
+    run: RunArgs,
+
+    /// Address to serve /metrics on
+    #[arg(long, default_value = "127.0.0.1:9469")]
+    listen: SocketAddr,
+
+    /// Time between scheduled tests, e.g. 90s, 15m or 1h
+    #[arg(long, default_value = "15m", value_parser = monitor::parse_interval)]
+    interval: Duration,
+
+    /// Longest random delay added to each scheduled test (defaults to a tenth
+    /// of the interval)
+    #[arg(long, value_parser = monitor::parse_interval, conflicts_with = "on_scrape")]
+    jitter: Option<Duration>,
+
+    /// Run a test when scraped instead of on a schedule
+    #[arg(long)]
+    on_scrape: bool,
+
+    /// With --on-scrape, reuse the last test for scrapes within this long
+    #[arg(long, default_value = "5m", value_parser = monitor::parse_interval)]
+    max_age: Duration,
+}
+
+#[derive(Args)]
+struct LatencyArgs {
+    #[command(flatten)]
+    target: TargetArgs,
+
+    /// Number of latency probes, including warmup
+    #[arg(short = 'n', long, value_parser = clap::value_parser!(u64).range(2..=1000))]
+    samples: Option<u64>,
+
+    /// Probes discarded before measuring
+    #[arg(long)]
+    warmup: Option<usize>,
+}
+
+impl LatencyArgs {
+    fn apply(&self, mut config: TestConfig) -> TestConfig {
+        config = self.target.apply(config);
+        if let Some(samples) = self.samples {
+            config.latency_samples = samples as usize;
+        }
+        if let Some(warmup) = self.warmup {
+            config.latency_warmup = warmup;
+        }
+        config
+    }
+}
+
+#[derive(Args)]
+struct LossArgs {
+    #[command(flatten)]
+    target: TargetArgs,
+
+    /// Number of probes to send
+    #[arg(short = 'n', long, value_parser = clap::value_parser!(u64).range(1..=10000))]
+    count: Option<u64>,
+
+    /// Seconds to wait for each probe before counting it as lost
+    #[arg(long)]
+    timeout: Option<f64>,
+
+    /// UDP echo server (host:port) for measuring real packet loss
+    #[arg(long)]
+    udp_echo: Option<String>,
+}
+
+impl LossArgs {
+    fn apply(&self, mut config: TestConfig) -> Result<TestConfig, String> {
+        config = self.target.apply(config);
+        if let Some(count) = self.count {
+            config.packet_loss_count = count as usize;
+        }
+        if let Some(timeout) = self.timeout {
+            config.packet_loss_timeout = Duration::try_from_secs_f64(timeout)
+                .map_err(|e| format!("invalid --timeout {timeout}: {e}"))?;
+        }
+        if let Some(ref addr) = self.udp_echo {
+            config.udp_echo_addr = Some(addr.clone());
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Args)]
+struct ServerArgs {
+    /// Address to listen on
+    #[arg(short, long, default_value = "0.0.0.0:8080")]
+    listen: SocketAddr,
+
+    /// Location name reported to clients (shown as "Server")
+    #[arg(long, default_value = "LOCAL")]
+    location: String,
+
+    /// Don't answer UDP packet loss probes on the listen address
+    #[arg(long)]
+    no_udp: bool,
+
+    /// Largest payload a single download request may ask for, in bytes
+    #[arg(long, default_value_t = DEFAULT_MAX_DOWNLOAD_BYTES)]
+    max_download_bytes: usize,
 }
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
-
-    let config = TestConfig {
-        parallel_connections: args.connections as usize,
-        skip_download: args.no_download,
-        skip_upload: args.no_upload,
-        ..TestConfig::default()
+    let cli = Cli::parse();
+    let out = Output {
+        format: cli.format,
+        header: !cli.no_header,
     };
 
-    let is_json = args.format == "json";
+    let config = || {
+        config_file::load(cli.config.as_deref(), cli.profile.as_deref()).unwrap_or_else(|e| fail(e))
+    };
 
-    let progress_cb = if is_json {
-        None
-    } else {
-        let pb = ProgressBar::new(100);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} {msg} [{bar:30.cyan/dim}] {pos}%")
-                .expect("valid template")
-                .progress_chars("=> "),
-        );
-        pb.set_message("Starting...");
-
-        let callback = move |update: ProgressUpdate| {
-            let msg = match update.phase {
-                TestPhase::Latency => {
-                    if let Some(ms) = update.latency_ms {
-                        format!("Measuring latency... {:.1} ms", ms)
-                    } else {
-                        "Measuring latency...".to_string()
-                    }
-                }
-                TestPhase::Download => {
-                    if let Some(speed) = update.speed_mbps {
-                        format!("Download: {:.2} Mbps", speed)
-                    } else {
-                        "Measuring download...".to_string()
-                    }
-                }
-                TestPhase::Upload => {
-                    if let Some(speed) = update.speed_mbps {
-                        format!("Upload: {:.2} Mbps", speed)
-                    } else {
-                        "Measuring upload...".to_string()
-                    }
-                }
-                TestPhase::PacketLoss => "Measuring packet loss...".to_string(),
-                TestPhase::Done => "Done!".to_string(),
+    match cli.command {
+        None => run_full(config(), &cli.run, out).await,
+        Some(Command::Run(ref args)) => run_full(config(), args, out).await,
+        Some(Command::Monitor(ref args)) => run_monitor(config(), args, out).await,
+        Some(Command::Exporter(ref args)) => run_exporter(config(), args).await,
+        Some(Command::Latency(ref args)) => {
+            run_single(Phase::Latency, &validated(args.apply(config())), out).await;
+        }
+        Some(Command::Download(ref args)) => {
+            run_single(Phase::Download, &validated(args.apply(config())), out).await;
+        }
+        Some(Command::Upload(ref args)) => {
+            run_single(Phase::Upload, &validated(args.apply(config())), out).await;
+        }
+        Some(Command::Loss(ref args)) => {
+            let config = args.apply(config()).unwrap_or_else(|e| fail(e));
+            run_single(Phase::Loss, &validated(config), out).await;
+        }
+        Some(Command::History { ref action }) => {
+            history::run(action, out).unwrap_or_else(|e| fail(e));
+        }
+        Some(Command::Config { ref action }) => {
+            config_file::run(action, cli.config.as_deref(), cli.profile.as_deref())
+                .unwrap_or_else(|e| fail(e));
+        }
+        Some(Command::Server(args)) => {
+            let config = ServerConfig {
+                location: args.location,
+                max_download_bytes: args.max_download_bytes,
             };
-            pb.set_message(msg);
-            pb.set_position((update.progress * 100.0) as u64);
+            if let Err(e) = tej_server::run(args.listen, config, !args.no_udp).await {
+                fail(e);
+            }
+        }
+    }
+}
 
-            if update.phase == TestPhase::Done {
-                pb.finish_and_clear();
+/// Runs the full test and saves the results to the history.
+async fn run_full(config: TestConfig, args: &RunArgs, out: Output) {
+    let config = validated(args.apply(config));
+
+    if out.is_text() {
+        println!("Tej - Honest Speed Test");
+        print_connections(&config);
+        println!();
+    }
+
+    let (progress, bar) = progress_bar(out);
+    let outcome =
+        tej_core::run_speed_tests_with_cancel(&config, progress, cancel_on_ctrl_c()).await;
+    if let Ok(ref results) = outcome {
+        if !args.no_history {
+            if let Err(e) = history::save(results) {
+                eprintln!("Warning: failed to save history: {e}");
             }
-        };
+        }
+    }
+    report(outcome, bar, out);
+}
 
-        Some(Arc::new(callback) as tej_core::ProgressCallback)
+/// Runs the full test on a schedule until the run count is reached or
+/// Ctrl-C. Exits with an error only if runs finished and none succeeded.
+async fn run_monitor(config: TestConfig, args: &MonitorArgs, out: Output) {
+    if args.interval.is_zero() {
+        fail("--interval must be greater than zero");
+    }
+    let config = validated(args.run.apply(config));
+    let options = monitor::MonitorOptions {
+        interval: args.interval,
+        jitter: args.jitter.unwrap_or(args.interval / 10),
+        count: args.count,
+        save_history: !args.run.no_history,
     };
 
-    if !is_json {
+    if out.is_text() {
         println!("Tej - Honest Speed Test");
+        println!(
+            "Testing every {} (+ up to {}), {}",
+            format_interval(options.interval),
+            format_interval(options.jitter),
+            match options.count {
+                Some(count) => format!("{count} runs"),
+                None => "until Ctrl-C".to_string(),
+            }
+        );
+        println!();
+    }
+
+    let tally = monitor::run(&config, &options, out, cancel_on_ctrl_c()).await;
+    if tally.finished > 0 && tally.succeeded == 0 {
+        fail("no run succeeded");
+    }
+}
+
+/// Serves Prometheus metrics until the process is stopped.
+async fn run_exporter(config: TestConfig, args: &ExporterArgs) {
+    let mode = if args.on_scrape {
+        exporter::Mode::OnScrape {
+            max_age: args.max_age,
+        }
+    } else {
+        if args.interval.is_zero() {
+            fail("--interval must be greater than zero");
+        }
+        exporter::Mode::Scheduled {
+            interval: args.interval,
+            jitter: args.jitter.unwrap_or(args.interval / 10),
+        }
+    };
+    let options = exporter::ExporterOptions {
+        listen: args.listen,
+        mode,
+        save_history: !args.run.no_history,
+    };
+
+    let config = validated(args.run.apply(config));
+    if let Err(e) = exporter::run(config, options).await {
+        fail(format!("failed to serve metrics on {}: {e}", args.listen));
+    }
+}
+
+fn format_interval(interval: Duration) -> String {
+    let secs = interval.as_secs_f64();
+    if secs >= 3600.0 && secs % 3600.0 == 0.0 {
+        format!("{}h", secs / 3600.0)
+    } else if secs >= 60.0 && secs % 60.0 == 0.0 {
+        format!("{}m", secs / 60.0)
+    } else {
+        format!("{secs}s")
+    }
+}
+
+/// Runs one phase on its own. Single-phase results aren't saved to history.
+async fn run_single(phase: Phase, config: &TestConfig, out: Output) {
+    if out.is_text() && matches!(phase, Phase::Download | Phase::Upload) {
+        print_connections(config);
+        println!();
+    }
+
+    let (progress, bar) = progress_bar(out);
+    let outcome = phase::run(phase, config, progress, cancel_on_ctrl_c()).await;
+    report(outcome, bar, out);
+}
+
+fn print_connections(config: &TestConfig) {
+    if config.adaptive_connections {
+        println!(
+            "Testing with up to {} adaptive connections...",
+            config.parallel_connections
+        );
+    } else {
         println!(
             "Testing with {} parallel connections...",
             config.parallel_connections
         );
-        println!();
     }
+}
+
+/// Ctrl-C stops the test and reports whatever phases completed.
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let on_ctrl_c = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            on_ctrl_c.cancel();
+        }
+    });
+    cancel
+}
+
+fn progress_bar(out: Output) -> (Option<ProgressCallback>, Option<ProgressBar>) {
+    if !out.is_text() {
+        return (None, None);
+    }
+
+    let pb = ProgressBar::new(100);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} {msg} [{bar:30.cyan/dim}] {pos}%")
+            .expect("valid template")
+            .progress_chars("=> "),
+    );
+    pb.set_message("Starting...");
+
+    let bar = pb.clone();
+    let callback = move |update: ProgressUpdate| {
+        let msg = match update.phase {
+            TestPhase::Connection => "Connecting...".to_string(),
+            TestPhase::Latency => {
+                if let Some(ms) = update.latency_ms {
+                    format!("Measuring latency... {:.1} ms", ms)
+                } else {
+                    "Measuring latency...".to_string()
+                }
+            }
+            TestPhase::Download | TestPhase::SingleStreamDownload => {
+                if let Some(speed) = update.speed_mbps {
+                    format!("Download: {:.2} Mbps", speed)
+                } else {
+                    "Measuring download...".to_string()
+                }
+            }
+            TestPhase::Upload | TestPhase::SingleStreamUpload => {
+                if let Some(speed) = update.speed_mbps {
+                    format!("Upload: {:.2} Mbps", speed)
+                } else {
+                    "Measuring upload...".to_string()
+                }
+            }
+            TestPhase::PacketLoss => "Measuring packet loss...".to_string(),
+            TestPhase::UdpPacketLoss => "Measuring UDP packet loss...".to_string(),
+            TestPhase::Done => "Done!".to_string(),
+        };
+        pb.set_message(msg);
+        pb.set_position((update.progress * 100.0) as u64);
+
+        if update.phase == TestPhase::Done {
+            pb.finish_and_clear();
+        }
+    };
+
+    (Some(Arc::new(callback) as ProgressCallback), Some(bar))
+}
 
-    match tej_core::run_speed_test(&config, progress_cb).await {
-        Ok(result) => {
-            if is_json {
-                output::print_json(&result);
-            } else {
-                display::print_results(&result);
+/// Prints the results, or the partial results of a cancelled test, and
+/// exits with a matching status on failure.
+fn report(outcome: tej_core::Result<Vec<SpeedTestResult>>, bar: Option<ProgressBar>, out: Output) {
+    match outcome {
+        Ok(results) => out.print(&results),
+        Err(SpeedTestError::Cancelled(partial)) => {
+            if let Some(pb) = bar {
+                pb.finish_and_clear();
             }
+            eprintln!("Test cancelled");
+            out.print(&[*partial]);
+            std::process::exit(130);
         }
-        Err(e) => {
-            eprintln!("Error: {e}");
-            std::process::exit(1);
+        Err(e) => fail(e),
+    }
+}
+
+/// Checks the settings merged from the config file, profile and flags,
+/// failing like a bad flag would.
+fn validated(config: TestConfig) -> TestConfig {
+    config.validate().unwrap_or_else(|e| fail(e));
+    config
+}
+
+fn fail(e: impl std::fmt::Display) -> ! {
+    eprintln!("Error: {e}");
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_bare_flags_run_the_full_test() {
+        let cli = Cli::try_parse_from(["tej", "-c", "4", "--no-upload"]).unwrap();
+        assert!(cli.command.is_none());
+        let config = cli.run.apply(TestConfig::default());
+        assert_eq!(config.parallel_connections, 4);
+        assert!(config.skip_upload);
+    }
+
+    #[test]
+    fn test_phase_subcommand_options() {
+        let cli = Cli::try_parse_from(["tej", "latency", "-n", "30", "--format", "json"]).unwrap();
+        assert_eq!(cli.format, Format::Json);
+        match cli.command {
+            Some(Command::Latency(args)) => {
+                assert_eq!(args.apply(TestConfig::default()).latency_samples, 30)
+            }
+            _ => panic!("expected the latency subcommand"),
         }
+        // Run options don't leak into single-phase subcommands
+        assert!(Cli::try_parse_from(["tej", "latency", "--no-upload"]).is_err());
     }
 }
@@ -0,0 +1,310 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use tej_core::{CancellationToken, SpeedTestError, SpeedTestResult, TestConfig};
+use tokio::time::Instant;
+
+use crate::history;
+use crate::output::Output;
+
+/// Runs included in the rolling summary.
+const SUMMARY_WINDOW: usize = 10;
+
+/// Parses an interval such as `90`, `30s`, `15m` or `1h`. A bare number is
+/// seconds.
+pub fn parse_interval(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let scale = match unit {
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return Err(format!("unknown unit `{unit}` (use s, m or h)")),
+    };
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration `{s}`"))?;
+    Duration::try_from_secs_f64(value * scale).map_err(|e| format!("invalid duration `{s}`: {e}"))
+}
+
+pub struct MonitorOptions {
+    pub interval: Duration,
+    /// Longest random delay added to each scheduled run
+    pub jitter: Duration,
+    /// Stop after this many runs; `None` runs until Ctrl-C
+    pub count: Option<u64>,
+    pub save_history: bool,
+}
+
+/// How many runs a monitoring session got through.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Tally {
+    /// Runs that finished, successfully or not. A run stopped by `cancel`
+    /// doesn't count.
+    pub finished: u64,
+    pub succeeded: u64,
+}
+
+/// Runs the test every `interval` until `count` runs have finished or
+/// `cancel` fires. Failed runs are reported and skipped rather than ending
+/// the loop. A run tests each address family `config.ip_version` selects,
+/// so it can produce more than one result. Text output gets a one-line
+/// result and rolling summary per run; other formats print each result as
+/// it arrives.
+pub async fn run(
+    config: &TestConfig,
+    options: &MonitorOptions,
+    mut out: Output,
+    cancel: CancellationToken,
+) -> Tally {
+    let mut schedule = Schedule::new(Instant::now(), options.interval);
+    let mut summary = Summary::default();
+    let mut runs = 0;
+
+    while options.count.is_none_or(|count| runs < count) {
+        let offset = options.jitter.mul_f64(rand::random::<f64>());
+        let at = schedule.next(Instant::now(), offset);
+        if cancel
+            .run_until_cancelled(tokio::time::sleep_until(at))
+            .await
+            .is_none()
+        {
+            break;
+        }
+
+        runs += 1;
+        match tej_core::run_speed_tests_with_cancel(config, None, cancel.clone()).await {
+            Ok(results) => {
+                if options.save_history {
+                    if let Err(e) = history::save(&results) {
+                        eprintln!("Warning: failed to save history: {e}");
+                    }
+                }
+                if out.is_text() {
+                    for result in &results {
+                        println!("Run {runs}: {}", format_run(result));
+                    }
+                } else {
+                    out.print_more(&results);
+                }
+                summary.push(Some(results));
+            }
+            // Stopped mid-run: the partial result isn't worth reporting
+            Err(SpeedTestError::Cancelled(_)) => break,
+            Err(e) => {
+                eprintln!("Run {runs} failed: {e}");
+                summary.push(None);
+            }
+        }
+
+        if out.is_text() {
+            println!("  {}", summary.format());
+        }
+    }
+
+    summary.tally
+}
+
+/// Fixed-rate schedule: run `n` is due at `start + n * interval`, plus a
+/// random offset so a fleet started together doesn't test in lockstep.
+pub struct Schedule {
+    start: Instant,
+    interval: Duration,
+    slot: u32,
+}
+
+impl Schedule {
+    pub fn new(start: Instant, interval: Duration) -> Self {
+        Self {
+            start,
+            interval,
+            slot: 0,
+        }
+    }
+
+    /// When the next run should start. Slots that passed while a slow run
+    /// was still going are skipped instead of running back-to-back.
+    pub fn next(&mut self, now: Instant, offset: Duration) -> Instant {
+        let mut at = self.start + self.interval * self.slot;
+        while at < now && self.slot > 0 {
+            self.slot += 1;
+            at = self.start + self.interval * self.slot;
+        }
+        self.slot += 1;
+        at + offset
+    }
+}
+
+/// Averages over the results of the last [`SUMMARY_WINDOW`] runs.
+#[derive(Default)]
+struct Summary {
+    window: VecDeque<Option<Vec<SpeedTestResult>>>,
+    tally: Tally,
+}
+
+impl Summary {
+    /// Records a finished run: its results, or `None` if it failed.
+    fn push(&mut self, results: Option<Vec<SpeedTestResult>>) {
+        self.tally.finished += 1;
+        if results.is_some() {
+            self.tally.succeeded += 1;
+        }
+        self.window.push_back(results);
+        if self.window.len() > SUMMARY_WINDOW {
+            self.window.pop_front();
+        }
+    }
+
+    fn format(&self) -> String {
+        let results: Vec<&SpeedTestResult> = self.window.iter().flatten().flatten().collect();
+        let failed = self.window.iter().filter(|run| run.is_none()).count();
+
+        let stats_of = |value: fn(&SpeedTestResult) -> Option<f64>| {
+            stats(results.iter().filter_map(|r| value(r)))
+        };
+
+        let mut parts = Vec::new();
+        if let Some((avg, min, max)) = stats_of(|r| r.download.as_ref().map(|d| d.mbps)) {
+            parts.push(format!("download {avg:.2} Mbps ({min:.2}-{max:.2})"));
+        }
+        if let Some((avg, min, max)) = stats_of(|r| r.upload.as_ref().map(|u| u.mbps)) {
+            parts.push(format!("upload {avg:.2} Mbps ({min:.2}-{max:.2})"));
+        }
+        if let Some((avg, _, max)) = stats_of(|r| r.latency.as_ref().map(|l| l.avg_ms)) {
+            parts.push(format!("latency {avg:.1} ms (max {max:.1})"));
+        }
+        if let Some((avg, _, _)) = stats_of(|r| r.packet_loss.as_ref().map(|p| p.loss_percent)) {
+            parts.push(format!("loss {avg:.1}%"));
+        }
+        if failed > 0 {
+            parts.push(format!("{failed} failed"));
+        }
+
+        format!("Last {} runs: {}", self.window.len(), parts.join(", "))
+    }
+}
+
+/// Average, minimum and maximum, or `None` without values.
+fn stats(values: impl Iterator<Item = f64>) -> Option<(f64, f64, f64)> {
+    let values: Vec<f64> = values.collect();
+    if values.is_empty() {
+        return None;
+    }
+    let avg = values.iter().sum::<f64>() / values.len() as f64;
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    Some((avg, min, max))
+}
+
+/// One-line summary of a single run.
+fn format_run(result: &SpeedTestResult) -> String {
+    let mut parts = vec![result
+        .timestamp
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()];
+    if let Some(family) = result.address_family {
+        parts.push(family.as_str().to_string());
+    }
+    if let Some(ref download) = result.download {
+        parts.push(format!("{:.2} Mbps down", download.mbps));
+    }
+    if let Some(ref upload) = result.upload {
+        parts.push(format!("{:.2} Mbps up", upload.mbps));
+    }
+    if let Some(ref latency) = result.latency {
+        parts.push(format!("{:.1} ms", latency.avg_ms));
+    }
+    if let Some(ref loss) = result.packet_loss {
+        parts.push(format!("{:.1}% loss", loss.loss_percent));
+    }
+    if !result.errors.is_empty() {
+        parts.push(format!("{} phases failed", result.errors.len()));
+    }
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tej_core::ThroughputResult;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_interval("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_interval("1.5h"), Ok(Duration::from_secs(5400)));
+        assert!(parse_interval("15 minutes").is_err());
+        assert!(parse_interval("m").is_err());
+    }
+
+    #[test]
+    fn test_schedule_skips_missed_slots() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(60);
+        let jitter = Duration::from_secs(5);
+        let mut schedule = Schedule::new(start, interval);
+
+        assert_eq!(schedule.next(start, jitter), start + jitter);
+        assert_eq!(
+            schedule.next(start + Duration::from_secs(10), jitter),
+            start + interval + jitter
+        );
+        // The second run took 150s: the slot at 120s is skipped
+        assert_eq!(
+            schedule.next(start + Duration::from_secs(150), Duration::ZERO),
+            start + interval * 3
+        );
+    }
+
+    fn result_with_download(mbps: u64) -> SpeedTestResult {
+        let mut result = SpeedTestResult::new();
+        result.download = Some(ThroughputResult::new(mbps * 125_000, 1.0));
+        result
+    }
+
+    #[test]
+    fn test_summary_window() {
+        let mut summary = Summary::default();
+        summary.push(None);
+        for mbps in 1..=SUMMARY_WINDOW as u64 {
+            summary.push(Some(vec![result_with_download(mbps)]));
+        }
+
+        // The failure has rolled out of the window
+        assert_eq!(
+            summary.tally,
+            Tally {
+                finished: SUMMARY_WINDOW as u64 + 1,
+                succeeded: SUMMARY_WINDOW as u64,
+            }
+        );
+        assert_eq!(
+            summary.format(),
+            format!("Last {SUMMARY_WINDOW} runs: download 5.50 Mbps (1.00-10.00)")
+        );
+    }
+
+    #[test]
+    fn test_dual_stack_run_counts_once() {
+        let mut summary = Summary::default();
+        summary.push(Some(vec![result_with_download(2), result_with_download(4)]));
+        summary.push(None);
+
+        assert_eq!(
+            summary.tally,
+            Tally {
+                finished: 2,
+                succeeded: 1,
+            }
+        );
+        assert_eq!(
+            summary.format(),
+            "Last 2 runs: download 3.00 Mbps (2.00-4.00), 1 failed"
+        );
+    }
+}
@@ -1,6 +1,325 @@
-use tej_core::SpeedTestResult;
+use std::io::{self, Write};
+
+use chrono::SecondsFormat;
+use clap::ValueEnum;
+use tej_core::{PacketLossResult, SpeedTestResult, ThroughputResult};
+
+use crate::display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human-readable tables
+    Text,
+    /// Pretty-printed JSON: one object, or an array for several results
+    Json,
+    /// One compact JSON object per line
+    Ndjson,
+    /// Flat columns described by `CSV_COLUMNS`
+    Csv,
+}
+
+/// How results are written to stdout.
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    pub format: Format,
+    /// Print the CSV header row
+    pub header: bool,
+}
+
+impl Output {
+    pub fn is_text(&self) -> bool {
+        self.format == Format::Text
+    }
+
+    /// Prints results in the selected format.
+    pub fn print(&self, results: &[SpeedTestResult]) {
+        match self.format {
+            Format::Text => {
+                for result in results {
+                    display::print_results(result);
+                }
+                if results.len() > 1 {
+                    display::print_comparison(results);
+                }
+            }
+            Format::Json => println!("{}", json_text(results, false)),
+            Format::Ndjson => {
+                for result in results {
+                    let json = serde_json::to_string(result).expect("Failed to serialize result");
+                    println!("{json}");
+                }
+            }
+            Format::Csv => {
+                let mut out = io::stdout().lock();
+                if let Err(e) = write_csv(&mut out, results, self.header) {
+                    // A closed pipe (e.g. `| head`) just ends the output
+                    if e.kind() != io::ErrorKind::BrokenPipe {
+                        eprintln!("Error: failed to write CSV: {e}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Like [`Output::print`], but for listings such as the history: JSON
+    /// is always an array, even for a single result.
+    pub fn print_list(&self, results: &[SpeedTestResult]) {
+        if self.format == Format::Json {
+            println!("{}", json_text(results, true));
+        } else {
+            self.print(results);
+        }
+    }
+
+    /// Like [`Output::print`], but for output that continues with more
+    /// results later: JSON prints each result on its own, and the CSV
+    /// header is printed only once.
+    pub fn print_more(&mut self, results: &[SpeedTestResult]) {
+        if self.format == Format::Json {
+            results.iter().for_each(print_json);
+        } else {
+            self.print(results);
+        }
+        self.header = false;
+    }
+}
 
 pub fn print_json(result: &SpeedTestResult) {
     let json = serde_json::to_string_pretty(result).expect("Failed to serialize result");
     println!("{json}");
 }
+
+/// Pretty-printed JSON: a single result as an object unless `list` is
+/// set, anything else as an array.
+fn json_text(results: &[SpeedTestResult], list: bool) -> String {
+    match results {
+        [result] if !list => serde_json::to_string_pretty(result),
+        _ => serde_json::to_string_pretty(results),
+    }
+    .expect("Failed to serialize results")
+}
+
+/// A CSV column: its header name and how to read it from a result. Missing
+/// values (skipped or failed phases) are written as empty cells.
+pub struct Column {
+    pub name: &'static str,
+    value: fn(&SpeedTestResult) -> Option<String>,
+}
+
+const fn column(name: &'static str, value: fn(&SpeedTestResult) -> Option<String>) -> Column {
+    Column { name, value }
+}
+
+/// CSV layout, in output order. Columns are only ever appended, never
+/// renamed, reordered or removed, so scripts can rely on positions; the
+/// README documents each one.
+pub const CSV_COLUMNS: &[Column] = &[
+    column("timestamp", |r| {
+        Some(r.timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }),
+    column("server_location", |r| r.server_location.clone()),
+    column("remote_ip", |r| r.remote_ip.map(|ip| ip.to_string())),
+    column("address_family", |r| {
+        r.address_family.map(|f| f.as_str().to_string())
+    }),
+    column("interface", |r| r.interface.clone()),
+    column("source_address", |r| {
+        r.source_address.map(|ip| ip.to_string())
+    }),
+    column("dns_ms", |r| r.connection.as_ref().map(|c| num(c.dns_ms))),
+    column("tcp_connect_ms", |r| {
+        r.connection.as_ref().map(|c| num(c.tcp_connect_ms))
+    }),
+    column("tls_handshake_ms", |r| {
+        r.connection.as_ref()?.tls_handshake_ms.map(num)
+    }),
+    column("ttfb_ms", |r| r.connection.as_ref().map(|c| num(c.ttfb_ms))),
+    column("latency_avg_ms", |r| {
+        r.latency.as_ref().map(|l| num(l.avg_ms))
+    }),
+    column("latency_median_ms", |r| {
+        r.latency.as_ref().map(|l| num(l.median_ms))
+    }),
+    column("latency_min_ms", |r| {
+        r.latency.as_ref().map(|l| num(l.min_ms))
+    }),
+    column("latency_max_ms", |r| {
+        r.latency.as_ref().map(|l| num(l.max_ms))
+    }),
+    column("latency_p95_ms", |r| {
+        r.latency.as_ref().map(|l| num(l.p95_ms))
+    }),
+    column("jitter_ms", |r| {
+        r.latency.as_ref().map(|l| num(l.jitter_ms))
+    }),
+    column("download_mbps", |r| {
+        r.download.as_ref().map(|t| num(t.mbps))
+    }),
+    column("download_raw_mbps", |r| {
+        r.download.as_ref().map(|t| num(t.raw_mbps))
+    }),
+    column("download_bytes", |r| {
+        r.download.as_ref().map(|t| t.bytes_transferred.to_string())
+    }),
+    column("download_connections", |r| {
+        r.download.as_ref().map(connection_count)
+    }),
+    column("download_fairness", |r| {
+        r.download.as_ref()?.fairness.map(num)
+    }),
+    column("download_loaded_latency_ms", |r| {
+        loaded_latency(r.download.as_ref())
+    }),
+    column("upload_mbps", |r| r.upload.as_ref().map(|t| num(t.mbps))),
+    column("upload_raw_mbps", |r| {
+        r.upload.as_ref().map(|t| num(t.raw_mbps))
+    }),
+    column("upload_bytes", |r| {
+        r.upload.as_ref().map(|t| t.bytes_transferred.to_string())
+    }),
+    column("upload_connections", |r| {
+        r.upload.as_ref().map(connection_count)
+    }),
+    column("upload_fairness", |r| r.upload.as_ref()?.fairness.map(num)),
+    column("upload_loaded_latency_ms", |r| {
+        loaded_latency(r.upload.as_ref())
+    }),
+    column("single_stream_download_mbps", |r| {
+        r.single_stream_download.as_ref().map(|t| num(t.mbps))
+    }),
+    column("single_stream_upload_mbps", |r| {
+        r.single_stream_upload.as_ref().map(|t| num(t.mbps))
+    }),
+    column("packet_loss_percent", |r| {
+        r.packet_loss.as_ref().map(|p| num(p.loss_percent))
+    }),
+    column("packet_loss_sent", |r| r.packet_loss.as_ref().map(sent)),
+    column("packet_loss_received", |r| {
+        r.packet_loss.as_ref().map(received)
+    }),
+    column("udp_loss_percent", |r| {
+        r.udp_packet_loss.as_ref().map(|p| num(p.loss_percent))
+    }),
+    column("udp_sent", |r| r.udp_packet_loss.as_ref().map(sent)),
+    column("udp_received", |r| r.udp_packet_loss.as_ref().map(received)),
+    column("udp_out_of_order", |r| {
+        r.udp_packet_loss
+            .as_ref()
+            .map(|p| p.out_of_order.to_string())
+    }),
+    column("udp_duplicates", |r| {
+        r.udp_packet_loss.as_ref().map(|p| p.duplicates.to_string())
+    }),
+    column("bufferbloat", |r| {
+        r.bufferbloat.map(|g| g.as_str().to_string())
+    }),
+    column("errors", |r| {
+        let errors: Vec<String> = r
+            .errors
+            .iter()
+            .map(|e| format!("{}: {}", e.phase.as_str(), e.error))
+            .collect();
+        (!errors.is_empty()).then(|| errors.join("; "))
+    }),
+];
+
+/// Writes results as CSV rows, preceded by the header row if `header` is set.
+pub fn write_csv(out: impl Write, results: &[SpeedTestResult], header: bool) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    if header {
+        writer.write_record(CSV_COLUMNS.iter().map(|c| c.name))?;
+    }
+    for result in results {
+        writer.write_record(
+            CSV_COLUMNS
+                .iter()
+                .map(|c| (c.value)(result).unwrap_or_default()),
+        )?;
+    }
+    writer.flush()
+}
+
+/// Fixed three decimals, enough for sub-microsecond timings and Kbps.
+fn num(value: f64) -> String {
+    format!("{value:.3}")
+}
+
+fn connection_count(throughput: &ThroughputResult) -> String {
+    throughput.connections.len().to_string()
+}
+
+fn loaded_latency(throughput: Option<&ThroughputResult>) -> Option<String> {
+    throughput?.loaded_latency.as_ref().map(|l| num(l.avg_ms))
+}
+
+fn sent(loss: &PacketLossResult) -> String {
+    loss.sent.to_string()
+}
+
+fn received(loss: &PacketLossResult) -> String {
+    loss.received.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use tej_core::{PhaseError, TestPhase};
+
+    fn csv_string(results: &[SpeedTestResult], header: bool) -> String {
+        let mut out = Vec::new();
+        write_csv(&mut out, results, header).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_csv_column_names_are_unique() {
+        let names: HashSet<_> = CSV_COLUMNS.iter().map(|c| c.name).collect();
+        assert_eq!(names.len(), CSV_COLUMNS.len());
+        assert_eq!(CSV_COLUMNS[0].name, "timestamp");
+    }
+
+    #[test]
+    fn test_csv_rows_match_header() {
+        let mut result = SpeedTestResult::new();
+        result.download = Some(ThroughputResult::new(1_250_000, 1.0));
+        result.errors.push(PhaseError {
+            phase: TestPhase::Upload,
+            error: "connection reset, retrying".to_string(),
+        });
+
+        let text = csv_string(&[result.clone(), result], true);
+        let mut reader = csv::Reader::from_reader(text.as_bytes());
+        let header = reader.headers().unwrap().clone();
+        assert_eq!(header.len(), CSV_COLUMNS.len());
+
+        let rows: Vec<_> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), 2);
+        let cell = |name: &str| {
+            let i = header.iter().position(|h| h == name).unwrap();
+            rows[0][i].to_string()
+        };
+        assert_eq!(cell("download_mbps"), "10.000");
+        assert_eq!(cell("upload_mbps"), "");
+        // The comma in the message is quoted, not a column break
+        assert_eq!(cell("errors"), "upload: connection reset, retrying");
+    }
+
+    #[test]
+    fn test_json_list_is_always_an_array() {
+        let one = [SpeedTestResult::new()];
+        let list: serde_json::Value = serde_json::from_str(&json_text(&one, true)).unwrap();
+        assert_eq!(list.as_array().map(Vec::len), Some(1));
+        let single: serde_json::Value = serde_json::from_str(&json_text(&one, false)).unwrap();
+        assert!(single.is_object());
+        let empty: serde_json::Value = serde_json::from_str(&json_text(&[], false)).unwrap();
+        assert_eq!(empty.as_array().map(Vec::len), Some(0));
+    }
+
+    #[test]
+    fn test_csv_without_header() {
+        let text = csv_string(&[SpeedTestResult::new()], false);
+        assert_eq!(text.lines().count(), 1);
+        assert!(!text.starts_with("timestamp"));
+    }
+}
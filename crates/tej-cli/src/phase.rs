@@ -0,0 +1,99 @@
+use tej_core::download::measure_download;
+use tej_core::latency::measure_latency;
+use tej_core::packet_loss::measure_packet_loss;
+use tej_core::runner::build_client;
+use tej_core::udp::measure_udp_loss;
+use tej_core::upload::measure_upload;
+use tej_core::{
+    AddressFamily, CancellationToken, ProgressCallback, ProgressUpdate, SpeedTestError,
+    SpeedTestResult, TestConfig, TestPhase,
+};
+
+/// A test phase run on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Latency,
+    Download,
+    Upload,
+    /// HTTP probe loss, plus UDP datagram loss when an echo server is set
+    Loss,
+}
+
+/// Runs `phase` once per address family selected by `config.ip_version`.
+/// Each result has only that phase (and the server details) filled in.
+pub async fn run(
+    phase: Phase,
+    config: &TestConfig,
+    progress: Option<ProgressCallback>,
+    cancel: CancellationToken,
+) -> tej_core::Result<Vec<SpeedTestResult>> {
+    let mut results = Vec::new();
+
+    for ip_version in config.ip_version.passes() {
+        let config = TestConfig {
+            ip_version,
+            ..config.clone()
+        };
+        let mut result = SpeedTestResult::new();
+        result.interface = config.interface.clone();
+        result.source_address = config.source_address;
+
+        let measured = cancel
+            .run_until_cancelled(measure(phase, &config, &mut result, progress.as_ref()))
+            .await;
+        match measured {
+            Some(outcome) => outcome?,
+            None => return Err(SpeedTestError::Cancelled(Box::new(result))),
+        }
+
+        if let Some(ref cb) = progress {
+            cb(ProgressUpdate {
+                phase: TestPhase::Done,
+                speed_mbps: None,
+                progress: 1.0,
+                latency_ms: None,
+            });
+        }
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+async fn measure(
+    phase: Phase,
+    config: &TestConfig,
+    result: &mut SpeedTestResult,
+    progress: Option<&ProgressCallback>,
+) -> tej_core::Result<()> {
+    let client = build_client(config)?;
+
+    match phase {
+        Phase::Latency => {
+            let (latency, server_location, remote_ip) =
+                measure_latency(&client, config, progress).await?;
+            result.latency = Some(latency);
+            result.server_location = server_location;
+            result.remote_ip = remote_ip;
+        }
+        Phase::Download => {
+            result.download = Some(measure_download(&client, config, progress).await?);
+        }
+        Phase::Upload => {
+            result.upload = Some(measure_upload(&client, config, progress).await?);
+        }
+        Phase::Loss => {
+            result.packet_loss = Some(measure_packet_loss(&client, config, progress).await?);
+            if config.udp_echo_addr.is_some() {
+                result.udp_packet_loss = Some(measure_udp_loss(config, progress).await?);
+            }
+        }
+    }
+
+    result.address_family = result
+        .remote_ip
+        .as_ref()
+        .map(AddressFamily::of)
+        .or(config.ip_version.family());
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+
+/// How long each connection count runs before deciding whether to add another.
+pub const ADAPTIVE_STEP: Duration = Duration::from_secs(1);
+
+/// Phase length in adaptive mode when `TestConfig::test_duration` is unset.
+pub const DEFAULT_ADAPTIVE_DURATION: Duration = Duration::from_secs(10);
+
+/// Minimum throughput gain from one more connection to keep adding them.
+const MIN_GAIN: f64 = 0.1;
+
+/// Where connection ramping stopped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RampOutcome {
+    /// Connections needed to saturate the link, if throughput levelled off
+    /// before the connection limit
+    pub saturating_connections: Option<usize>,
+    /// Seconds from the start of the phase until the last connection was added
+    /// and had a step to ramp up
+    pub settled_secs: f64,
+}
+
+/// Adds connections one at a time while aggregate throughput keeps improving.
+///
+/// The first connection must already be running. Every [`ADAPTIVE_STEP`] the
+/// throughput of the last step is compared with the one before; while it grew
+/// by at least 10%, `spawn` is called with the next connection index, up to
+/// `max_connections`. Ramping stops halfway to `deadline` so the settled
+/// connection count still gets a steady-state window.
+pub async fn ramp_connections<F>(
+    max_connections: usize,
+    total_bytes: &AtomicU64,
+    start: Instant,
+    deadline: Instant,
+    mut spawn: F,
+) -> Result<RampOutcome>
+where
+    F: FnMut(usize) -> Result<()>,
+{
+    let cutoff = start + (deadline - start) / 2;
+    let mut ramp = Ramp::new(max_connections);
+    let mut step_start_bytes = total_bytes.load(Ordering::Acquire);
+
+    loop {
+        tokio::time::sleep(ADAPTIVE_STEP).await;
+        let bytes = total_bytes.load(Ordering::Acquire);
+        let step_bps =
+            bytes.saturating_sub(step_start_bytes) as f64 * 8.0 / ADAPTIVE_STEP.as_secs_f64();
+        step_start_bytes = bytes;
+
+        match ramp.after_step(step_bps, Instant::now() >= cutoff) {
+            Some(index) => spawn(index)?,
+            None => {
+                return Ok(RampOutcome {
+                    saturating_connections: ramp.saturating_connections,
+                    settled_secs: start.elapsed().as_secs_f64(),
+                })
+            }
+        }
+    }
+}
+
+/// Decision state for [`ramp_connections`], kept apart from the timing so it
+/// can be tested directly.
+struct Ramp {
+    max_connections: usize,
+    connections: usize,
+    prev_bps: Option<f64>,
+    saturating_connections: Option<usize>,
+}
+
+impl Ramp {
+    fn new(max_connections: usize) -> Self {
+        Self {
+            max_connections,
+            connections: 1,
+            prev_bps: None,
+            saturating_connections: None,
+        }
+    }
+
+    /// Records one step's throughput and returns the index of the connection
+    /// to add next, or `None` once ramping is over.
+    fn after_step(&mut self, step_bps: f64, past_cutoff: bool) -> Option<usize> {
+        if let Some(prev) = self.prev_bps {
+            if step_bps <= prev * (1.0 + MIN_GAIN) {
+                // The last connection added nothing, so the link was
+                // already saturated without it
+                self.saturating_connections = Some(self.connections - 1);
+                return None;
+            }
+        }
+        if self.connections >= self.max_connections || past_cutoff {
+            return None;
+        }
+        self.prev_bps = Some(step_bps);
+        self.connections += 1;
+        Some(self.connections - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ramp_adds_while_throughput_grows() {
+        let mut ramp = Ramp::new(8);
+        assert_eq!(ramp.after_step(10.0, false), Some(1));
+        assert_eq!(ramp.after_step(20.0, false), Some(2));
+        assert_eq!(ramp.after_step(28.0, false), Some(3));
+        // Under 10% better than 28
+        assert_eq!(ramp.after_step(30.0, false), None);
+        assert_eq!(ramp.saturating_connections, Some(3));
+    }
+
+    #[test]
+    fn test_ramp_stops_at_connection_limit() {
+        let mut ramp = Ramp::new(2);
+        assert_eq!(ramp.after_step(10.0, false), Some(1));
+        assert_eq!(ramp.after_step(20.0, false), None);
+        assert_eq!(ramp.saturating_connections, None);
+    }
+
+    #[test]
+    fn test_ramp_stops_at_cutoff() {
+        let mut ramp = Ramp::new(8);
+        assert_eq!(ramp.after_step(10.0, true), None);
+        assert_eq!(ramp.saturating_connections, None);
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt::Debug;
+
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Request, RequestBuilder};
+
+use crate::config::TestConfig;
+use crate::error::{Result, SpeedTestError};
+
+/// Speed test server protocol: how to build download, upload and latency
+/// requests against the URLs in `TestConfig`, and how to read server
+/// metadata from responses.
+///
+/// Implement this to test against servers that don't speak Cloudflare's
+/// `__down?bytes=N` / `__up` protocol.
+pub trait SpeedTestBackend: Debug + Send + Sync {
+    /// Request that streams `bytes` bytes of incompressible data back.
+    fn download_request(
+        &self,
+        client: &Client,
+        config: &TestConfig,
+        bytes: usize,
+    ) -> RequestBuilder;
+
+    /// Request that accepts an uploaded payload. The caller attaches the body.
+    fn upload_request(&self, client: &Client, config: &TestConfig) -> RequestBuilder;
+
+    /// Minimal request used for RTT probes.
+    fn latency_request(&self, client: &Client, config: &TestConfig) -> RequestBuilder;
+
+    /// Extracts the serving location (datacenter, site name) from response headers.
+    fn server_location(&self, headers: &HeaderMap) -> Option<String>;
+}
+
+/// Cloudflare's speed test protocol (`speed.cloudflare.com`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CloudflareBackend;
+
+impl SpeedTestBackend for CloudflareBackend {
+    fn download_request(
+        &self,
+        client: &Client,
+        config: &TestConfig,
+        bytes: usize,
+    ) -> RequestBuilder {
+        client.get(format!("{}?bytes={}", config.download_url, bytes))
+    }
+
+    fn upload_request(&self, client: &Client, config: &TestConfig) -> RequestBuilder {
+        client
+            .post(&config.upload_url)
+            .header("Content-Type", "application/octet-stream")
+    }
+
+    fn latency_request(&self, client: &Client, config: &TestConfig) -> RequestBuilder {
+        client.get(format!("{}?bytes=0", config.latency_url))
+    }
+
+    fn server_location(&self, headers: &HeaderMap) -> Option<String> {
+        // cf-ray format: "hex-LOCATION"
+        let ray = headers.get("cf-ray")?.to_str().ok()?;
+        ray.rsplit('-').next().map(str::to_string)
+    }
+}
+
+/// Copies a bodiless request built by a backend so spawned tasks can re-send it.
+pub(crate) fn clone_request(request: &Request) -> Result<Request> {
+    request.try_clone().ok_or_else(|| {
+        SpeedTestError::Other(format!("Request to {} cannot be cloned", request.url()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_cloudflare_location_from_cf_ray() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cf-ray", HeaderValue::from_static("8a1b2c3d4e5f6789-SFO"));
+        assert_eq!(
+            CloudflareBackend.server_location(&headers),
+            Some("SFO".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cloudflare_location_missing_header() {
+        assert_eq!(CloudflareBackend.server_location(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_cloudflare_download_url() {
+        let config = TestConfig::default();
+        let request = CloudflareBackend
+            .download_request(&Client::new(), &config, 1000)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://speed.cloudflare.com/__down?bytes=1000"
+        );
+    }
+}
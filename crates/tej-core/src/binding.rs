@@ -0,0 +1,163 @@
+use std::net::{IpAddr, SocketAddr};
+
+use reqwest::ClientBuilder;
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+
+use crate::config::TestConfig;
+use crate::error::{Result, SpeedTestError};
+
+/// Local address outgoing sockets bind to: the configured source address,
+/// or an unspecified address of the forced family.
+fn local_address(config: &TestConfig) -> Result<Option<IpAddr>> {
+    match config.source_address {
+        Some(source) => {
+            let addr = SocketAddr::new(source, 0);
+            if !config.ip_version.allows(&addr) {
+                return Err(SpeedTestError::Other(format!(
+                    "Source address {source} is not {}",
+                    config.ip_version
+                )));
+            }
+            Ok(Some(source))
+        }
+        None => Ok(config.ip_version.local_address()),
+    }
+}
+
+/// Whether a resolved server address is reachable from the configured
+/// address family and source address.
+pub fn allows(config: &TestConfig, addr: &SocketAddr) -> bool {
+    let same_family = config
+        .source_address
+        .is_none_or(|source| source.is_ipv4() == addr.is_ipv4());
+    config.ip_version.allows(addr) && same_family
+}
+
+/// Applies the source address and interface binding to the shared client.
+pub fn configure_client(builder: ClientBuilder, config: &TestConfig) -> Result<ClientBuilder> {
+    let builder = builder.local_address(local_address(config)?);
+    match config.interface {
+        Some(ref interface) => bind_client_interface(builder, interface),
+        None => Ok(builder),
+    }
+}
+
+/// Opens a TCP connection honouring the source address and interface binding.
+pub async fn connect_tcp(config: &TestConfig, addr: SocketAddr) -> Result<TcpStream> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    if let Some(source) = local_address(config)? {
+        socket.bind(SocketAddr::new(source, 0))?;
+    }
+    if let Some(ref interface) = config.interface {
+        bind_tcp_interface(&socket, interface)?;
+    }
+    Ok(socket.connect(addr).await?)
+}
+
+/// Binds a UDP socket for talking to `remote`, honouring the source address
+/// and interface binding.
+pub async fn bind_udp(config: &TestConfig, remote: SocketAddr) -> Result<UdpSocket> {
+    let local = match local_address(config)? {
+        Some(source) => SocketAddr::new(source, 0),
+        None if remote.is_ipv4() => SocketAddr::from(([0, 0, 0, 0], 0)),
+        None => SocketAddr::from(([0u16; 8], 0)),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    if let Some(ref interface) = config.interface {
+        bind_udp_interface(&socket, interface)?;
+    }
+    Ok(socket)
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_client_interface(builder: ClientBuilder, interface: &str) -> Result<ClientBuilder> {
+    Ok(builder.interface(interface))
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_tcp_interface(socket: &TcpSocket, interface: &str) -> Result<()> {
+    socket
+        .bind_device(Some(interface.as_bytes()))
+        .map_err(|e| interface_error(interface, e))
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_udp_interface(socket: &UdpSocket, interface: &str) -> Result<()> {
+    socket
+        .bind_device(Some(interface.as_bytes()))
+        .map_err(|e| interface_error(interface, e))
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn interface_error(interface: &str, e: std::io::Error) -> SpeedTestError {
+    SpeedTestError::Other(format!("Cannot bind to interface {interface}: {e}"))
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_client_interface(_builder: ClientBuilder, _interface: &str) -> Result<ClientBuilder> {
+    Err(interface_unsupported())
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_tcp_interface(_socket: &TcpSocket, _interface: &str) -> Result<()> {
+    Err(interface_unsupported())
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_udp_interface(_socket: &UdpSocket, _interface: &str) -> Result<()> {
+    Err(interface_unsupported())
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn interface_unsupported() -> SpeedTestError {
+    SpeedTestError::Other(
+        "Binding to a network interface is only supported on Linux; use a source address instead"
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IpVersion;
+
+    #[test]
+    fn test_source_address_must_match_ip_version() {
+        let config = TestConfig {
+            source_address: Some("127.0.0.1".parse().unwrap()),
+            ip_version: IpVersion::V6,
+            ..TestConfig::default()
+        };
+        assert!(local_address(&config).is_err());
+    }
+
+    #[test]
+    fn test_source_address_filters_server_family() {
+        let config = TestConfig {
+            source_address: Some("127.0.0.1".parse().unwrap()),
+            ..TestConfig::default()
+        };
+        assert!(allows(&config, &"10.0.0.1:80".parse().unwrap()));
+        assert!(!allows(&config, &"[::1]:80".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_connect_from_source_address() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = TestConfig {
+            source_address: Some("127.0.0.1".parse().unwrap()),
+            ..TestConfig::default()
+        };
+        let stream = connect_tcp(&config, listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            stream.local_addr().unwrap().ip(),
+            config.source_address.unwrap()
+        );
+    }
+}
@@ -1,29 +1,166 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
 use std::time::Duration;
 
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+use crate::adaptive::DEFAULT_ADAPTIVE_DURATION;
+use crate::backend::{CloudflareBackend, SpeedTestBackend};
+use crate::error::{Result, SpeedTestError};
+use crate::results::AddressFamily;
+
+/// Most connections a transfer phase may open.
+pub const MAX_CONNECTIONS: usize = 32;
+
+/// Settings for a speed test run.
+///
+/// Serializes to a flat table (the format of tej-cli's TOML config file);
+/// durations are written as seconds and missing fields take their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct TestConfig {
+    /// Server protocol used to build requests against the URLs below
+    #[serde(skip)]
+    pub backend: Arc<dyn SpeedTestBackend>,
     pub download_url: String,
     pub upload_url: String,
     pub latency_url: String,
+    /// Connections per download/upload phase, or the upper limit in adaptive mode
     pub parallel_connections: usize,
+    /// Start each transfer phase with one connection and add more while
+    /// throughput keeps improving, up to `parallel_connections`. Runs as a
+    /// timed phase, for `test_duration` or 10 seconds by default.
+    pub adaptive_connections: bool,
+    /// Also run download and upload over a single connection, to compare
+    /// with the parallel result
+    pub compare_single_stream: bool,
     pub download_sizes: Vec<usize>,
     pub upload_size: usize,
     pub latency_samples: usize,
     pub latency_warmup: usize,
+    /// Delay between latency probes sent during download/upload phases
+    #[serde(with = "secs")]
+    pub loaded_latency_interval: Duration,
+    #[serde(with = "secs")]
     pub timeout: Duration,
+    /// Time budget per download/upload phase. When set, each connection keeps
+    /// re-issuing requests until it elapses instead of transferring a fixed size.
+    #[serde(with = "opt_secs")]
+    pub test_duration: Option<Duration>,
+    /// Start of a timed phase excluded from the measurement to skip TCP slow-start
+    #[serde(with = "secs")]
+    pub ramp_up: Duration,
+    /// Fraction of a fixed-size transfer's duration discarded as ramp-up (0.0-0.9)
+    pub steady_state_skip: f64,
     pub packet_loss_count: usize,
+    #[serde(with = "secs")]
     pub packet_loss_timeout: Duration,
+    /// `host:port` of a UDP echo server (such as `tej-server`) for measuring
+    /// real datagram loss. Skipped when unset.
+    pub udp_echo_addr: Option<String>,
+    /// Address family used to reach the server
+    pub ip_version: IpVersion,
+    /// Local address to send test traffic from, selecting the interface
+    /// that owns it
+    pub source_address: Option<IpAddr>,
+    /// Network interface to bind test sockets to (Linux only)
+    pub interface: Option<String>,
     pub skip_download: bool,
     pub skip_upload: bool,
+    /// Record a failed phase on the result and keep running the remaining
+    /// phases, instead of failing the whole test
+    pub continue_on_error: bool,
+}
+
+impl TestConfig {
+    /// Checks that the settings describe a test that can run, such as after
+    /// merging a config file, profile and command-line flags.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: String| Err(SpeedTestError::InvalidConfig(message));
+        if self.download_sizes.is_empty() {
+            return invalid("download_sizes must not be empty".to_string());
+        }
+        if self.download_sizes.contains(&0) || self.upload_size == 0 {
+            return invalid("transfer sizes must be greater than zero".to_string());
+        }
+        if !(1..=MAX_CONNECTIONS).contains(&self.parallel_connections) {
+            return invalid(format!(
+                "parallel_connections must be between 1 and {MAX_CONNECTIONS}, got {}",
+                self.parallel_connections
+            ));
+        }
+        if self.latency_warmup >= self.latency_samples {
+            return invalid(format!(
+                "latency_warmup ({}) must be less than latency_samples ({})",
+                self.latency_warmup, self.latency_samples
+            ));
+        }
+        if self.packet_loss_count == 0 {
+            return invalid("packet_loss_count must be greater than zero".to_string());
+        }
+        if self.test_duration.is_some_and(|d| d.is_zero()) {
+            return invalid("test_duration must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+
+    /// Copy of this config for the single-connection comparison run.
+    pub fn single_stream(&self) -> Self {
+        Self {
+            parallel_connections: 1,
+            adaptive_connections: false,
+            ..self.clone()
+        }
+    }
+
+    /// Time budget of each download/upload phase, if the phases are timed.
+    pub fn phase_duration(&self) -> Option<Duration> {
+        match self.test_duration {
+            Some(duration) => Some(duration),
+            None if self.adaptive_connections => Some(DEFAULT_ADAPTIVE_DURATION),
+            None => None,
+        }
+    }
+
+    /// Default settings pointed at a server speaking the Cloudflare protocol
+    /// at `base_url`, such as `tej-server`. UDP probes go to the same host
+    /// and port, where `tej-server` runs its echo endpoint.
+    pub fn for_server(base_url: &str) -> Self {
+        Self::default().with_server(base_url)
+    }
+
+    /// Points the test URLs and UDP echo address at `base_url`, keeping
+    /// every other setting.
+    pub fn with_server(self, base_url: &str) -> Self {
+        let base = base_url.trim_end_matches('/');
+        // host_str() keeps IPv6 brackets, so this is always a valid socket address
+        let udp_echo_addr = reqwest::Url::parse(base).ok().and_then(|url| {
+            Some(format!(
+                "{}:{}",
+                url.host_str()?,
+                url.port_or_known_default()?
+            ))
+        });
+        Self {
+            download_url: format!("{base}/__down"),
+            upload_url: format!("{base}/__up"),
+            latency_url: format!("{base}/__down"),
+            udp_echo_addr,
+            ..self
+        }
+    }
 }
 
 impl Default for TestConfig {
     fn default() -> Self {
         Self {
+            backend: Arc::new(CloudflareBackend),
             download_url: "https://speed.cloudflare.com/__down".to_string(),
             upload_url: "https://speed.cloudflare.com/__up".to_string(),
             latency_url: "https://speed.cloudflare.com/__down".to_string(),
             parallel_connections: 6,
+            adaptive_connections: false,
+            compare_single_stream: false,
             download_sizes: vec![
                 100_000,    // 100KB warmup
                 1_000_000,  // 1MB
@@ -33,13 +170,128 @@ impl Default for TestConfig {
             upload_size: 10_000_000, // 10MB
             latency_samples: 20,
             latency_warmup: 3,
+            loaded_latency_interval: Duration::from_millis(250),
             timeout: Duration::from_secs(30),
+            test_duration: None,
+            ramp_up: Duration::from_secs(1),
+            steady_state_skip: 0.2,
             packet_loss_count: 20,
             packet_loss_timeout: Duration::from_secs(2),
+            udp_echo_addr: None,
+            ip_version: IpVersion::Any,
+            source_address: None,
+            interface: None,
             skip_download: false,
             skip_upload: false,
+            continue_on_error: false,
+        }
+    }
+}
+
+/// Which address family to test over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpVersion {
+    /// Let the OS pick from the resolved addresses
+    #[default]
+    Any,
+    /// IPv4 only
+    V4,
+    /// IPv6 only
+    V6,
+    /// IPv4 and IPv6 back-to-back with [`crate::run_speed_tests_with_cancel`].
+    /// Single-run entry points treat it like `Any`.
+    Both,
+}
+
+impl IpVersion {
+    /// The single-family runs this setting expands to.
+    pub fn passes(self) -> Vec<IpVersion> {
+        match self {
+            IpVersion::Both => vec![IpVersion::V4, IpVersion::V6],
+            other => vec![other],
+        }
+    }
+
+    /// Whether `addr` may be used under this setting.
+    pub fn allows(self, addr: &SocketAddr) -> bool {
+        match self {
+            IpVersion::V4 => addr.is_ipv4(),
+            IpVersion::V6 => addr.is_ipv6(),
+            IpVersion::Any | IpVersion::Both => true,
+        }
+    }
+
+    /// The family this setting forces, if any.
+    pub fn family(self) -> Option<AddressFamily> {
+        match self {
+            IpVersion::V4 => Some(AddressFamily::Ipv4),
+            IpVersion::V6 => Some(AddressFamily::Ipv6),
+            IpVersion::Any | IpVersion::Both => None,
+        }
+    }
+
+    /// Unspecified local address that restricts outgoing connections to this
+    /// family, or `None` to leave the choice to the OS.
+    pub fn local_address(self) -> Option<IpAddr> {
+        match self {
+            IpVersion::V4 => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            IpVersion::V6 => Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+            IpVersion::Any | IpVersion::Both => None,
+        }
+    }
+}
+
+impl std::fmt::Display for IpVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            IpVersion::Any => "IPv4 or IPv6",
+            IpVersion::V4 => "IPv4",
+            IpVersion::V6 => "IPv6",
+            IpVersion::Both => "IPv4 and IPv6",
+        })
+    }
+}
+
+/// Serde adapter writing a `Duration` as fractional seconds.
+mod secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Like [`secs`], for optional durations.
+mod opt_secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_some(&duration.as_secs_f64()),
+            None => serializer.serialize_none(),
         }
     }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<f64>::deserialize(deserializer)?
+            .map(|secs| Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom))
+            .transpose()
+    }
 }
 
 #[cfg(test)]
@@ -64,4 +316,107 @@ mod tests {
         let config = TestConfig::default();
         assert!(config.parallel_connections >= 1 && config.parallel_connections <= 32);
     }
+
+    #[test]
+    fn test_validate_rejects_unusable_values() {
+        assert!(TestConfig::default().validate().is_ok());
+
+        let invalid = [
+            TestConfig {
+                download_sizes: vec![],
+                ..TestConfig::default()
+            },
+            TestConfig {
+                parallel_connections: 0,
+                ..TestConfig::default()
+            },
+            TestConfig {
+                parallel_connections: MAX_CONNECTIONS + 1,
+                ..TestConfig::default()
+            },
+            TestConfig {
+                latency_samples: 3,
+                latency_warmup: 3,
+                ..TestConfig::default()
+            },
+            TestConfig {
+                test_duration: Some(Duration::ZERO),
+                ..TestConfig::default()
+            },
+        ];
+        for config in invalid {
+            assert!(matches!(
+                config.validate(),
+                Err(SpeedTestError::InvalidConfig(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_for_server_urls() {
+        let config = TestConfig::for_server("http://10.0.0.5:8080/");
+        assert_eq!(config.download_url, "http://10.0.0.5:8080/__down");
+        assert_eq!(config.upload_url, "http://10.0.0.5:8080/__up");
+        assert_eq!(config.latency_url, "http://10.0.0.5:8080/__down");
+        assert_eq!(config.udp_echo_addr.as_deref(), Some("10.0.0.5:8080"));
+    }
+
+    #[test]
+    fn test_for_server_ipv6_udp_addr() {
+        let config = TestConfig::for_server("http://[::1]:9000");
+        assert_eq!(config.udp_echo_addr.as_deref(), Some("[::1]:9000"));
+    }
+
+    #[test]
+    fn test_default_config_is_size_bounded() {
+        let config = TestConfig::default();
+        assert!(config.test_duration.is_none());
+        assert!(config.ramp_up > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_ip_version_filters_addresses() {
+        let v4: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        let v6: SocketAddr = "[::1]:80".parse().unwrap();
+        assert!(IpVersion::V4.allows(&v4) && !IpVersion::V4.allows(&v6));
+        assert!(IpVersion::V6.allows(&v6) && !IpVersion::V6.allows(&v4));
+        assert!(IpVersion::Any.allows(&v4) && IpVersion::Any.allows(&v6));
+        assert_eq!(IpVersion::Both.passes(), vec![IpVersion::V4, IpVersion::V6]);
+    }
+
+    #[test]
+    fn test_adaptive_mode_is_timed() {
+        let config = TestConfig {
+            adaptive_connections: true,
+            ..TestConfig::default()
+        };
+        assert_eq!(config.phase_duration(), Some(DEFAULT_ADAPTIVE_DURATION));
+    }
+
+    #[test]
+    fn test_config_serde_roundtrip_with_defaults() {
+        let config: TestConfig = serde_json::from_str(
+            r#"{"parallel_connections": 4, "timeout": 2.5, "test_duration": 10, "ip_version": "v6"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.parallel_connections, 4);
+        assert_eq!(config.timeout, Duration::from_millis(2500));
+        assert_eq!(config.test_duration, Some(Duration::from_secs(10)));
+        assert_eq!(config.ip_version, IpVersion::V6);
+        // Unset fields keep their defaults
+        assert_eq!(
+            config.latency_samples,
+            TestConfig::default().latency_samples
+        );
+
+        let json = serde_json::to_string(&config).unwrap();
+        let back: TestConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.timeout, config.timeout);
+        assert!(!json.contains("backend"));
+    }
+
+    #[test]
+    fn test_config_rejects_negative_duration() {
+        assert!(serde_json::from_str::<TestConfig>(r#"{"timeout": -1}"#).is_err());
+    }
 }
@@ -5,10 +5,17 @@ use std::time::Instant;
 use futures::stream::StreamExt;
 use tokio::task::JoinSet;
 
+use crate::adaptive::ramp_connections;
+use crate::backend::clone_request;
 use crate::config::TestConfig;
 use crate::error::Result;
+use crate::latency::LoadedLatencyProbe;
 use crate::progress::{ProgressCallback, ProgressUpdate, TestPhase};
-use crate::results::ThroughputResult;
+use crate::results::{ConnectionStats, ThroughputResult};
+use crate::sampler::{
+    progress_reporter, ramp_up_secs, steady_state_bps, throughput_samples, ByteSample,
+    TransferMonitor,
+};
 
 pub async fn measure_download(
     client: &reqwest::Client,
@@ -18,8 +25,9 @@ pub async fn measure_download(
     // Warmup with small download to estimate speed
     let warmup_size = config.download_sizes[0];
     let warmup_start = Instant::now();
-    let resp = client
-        .get(format!("{}?bytes={}", config.download_url, warmup_size))
+    let resp = config
+        .backend
+        .download_request(client, config, warmup_size)
         .send()
         .await?;
     let mut warmup_bytes = 0u64;
@@ -44,70 +52,234 @@ pub async fn measure_download(
     };
 
     // Run parallel downloads
+    let probe = LoadedLatencyProbe::start(client, config)?;
+    let request = config
+        .backend
+        .download_request(client, config, test_size)
+        .build()?;
     let total_bytes = Arc::new(AtomicU64::new(0));
     let start = Instant::now();
-    let mut tasks = JoinSet::new();
+    let deadline = config.phase_duration().map(|d| start + d);
+    let mut tasks: JoinSet<(ConnectionStats, Result<()>)> = JoinSet::new();
 
-    for _ in 0..config.parallel_connections {
+    let spawn_connection = |tasks: &mut JoinSet<_>, index: usize| -> Result<()> {
         let client = client.clone();
-        let url = format!("{}?bytes={}", config.download_url, test_size);
+        let request = clone_request(&request)?;
         let total = total_bytes.clone();
+        // Adaptive mode opens connections late; time each from its own start
+        let conn_start = Instant::now();
 
         tasks.spawn(async move {
-            let resp = client.get(&url).send().await?;
-            let mut stream = resp.bytes_stream();
-            while let Some(chunk) = stream.next().await {
-                let chunk = chunk?;
-                total.fetch_add(chunk.len() as u64, Ordering::Release);
-            }
-            Ok::<(), crate::error::SpeedTestError>(())
+            let mut conn_bytes = 0u64;
+            let transfer = async {
+                // In timed mode keep re-issuing requests until the deadline cuts us off
+                loop {
+                    let resp = client.execute(clone_request(&request)?).await?;
+                    let mut stream = resp.bytes_stream();
+                    while let Some(chunk) = stream.next().await {
+                        let chunk = chunk?;
+                        conn_bytes += chunk.len() as u64;
+                        total.fetch_add(chunk.len() as u64, Ordering::Release);
+                    }
+                    if deadline.is_none() {
+                        break;
+                    }
+                }
+                Ok::<(), crate::error::SpeedTestError>(())
+            };
+            let outcome = match deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline.into(), transfer)
+                    .await
+                    .unwrap_or(Ok(())),
+                None => transfer.await,
+            };
+            // Bytes already received count even if the connection failed
+            let mut stats =
+                ConnectionStats::new(index, conn_bytes, conn_start.elapsed().as_secs_f64());
+            stats.error = outcome.as_ref().err().map(ToString::to_string);
+            (stats, outcome)
         });
-    }
+        Ok(())
+    };
 
-    // Progress reporting loop
-    let progress_total = total_bytes.clone();
+    // Sample the byte counter for progress reporting and steady-state throughput
     let expected_total = (test_size * config.parallel_connections) as f64;
-    let progress_handle = if let Some(cb) = progress {
-        let cb = cb.clone();
-        Some(tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(std::time::Duration::from_millis(250)).await;
-                let bytes = progress_total.load(Ordering::Acquire);
-                let elapsed = start.elapsed().as_secs_f64();
-                let speed_bps = (bytes as f64 * 8.0) / elapsed;
-                cb(ProgressUpdate {
-                    phase: TestPhase::Download,
-                    speed_mbps: Some(speed_bps / 1_000_000.0),
-                    progress: (bytes as f64 / expected_total).min(1.0),
-                    latency_ms: None,
-                });
+    let monitor = TransferMonitor::start(
+        total_bytes.clone(),
+        start,
+        progress_reporter(
+            progress,
+            TestPhase::Download,
+            expected_total,
+            config.phase_duration(),
+        ),
+    );
+
+    let ramp = match deadline {
+        Some(deadline) if config.adaptive_connections => {
+            spawn_connection(&mut tasks, 0)?;
+            let ramp = ramp_connections(
+                config.parallel_connections,
+                &total_bytes,
+                start,
+                deadline,
+                |index| spawn_connection(&mut tasks, index),
+            )
+            .await?;
+            Some(ramp)
+        }
+        _ => {
+            for index in 0..config.parallel_connections {
+                spawn_connection(&mut tasks, index)?;
             }
-        }))
-    } else {
-        None
+            None
+        }
     };
 
-    // Wait for all downloads to complete
+    // A failed connection keeps its partial bytes; the phase only fails
+    // when no connection succeeded
+    let mut connections = Vec::with_capacity(config.parallel_connections);
+    let mut first_error = None;
     while let Some(result) = tasks.join_next().await {
-        result.map_err(|e| crate::error::SpeedTestError::Other(e.to_string()))??;
+        let (stats, outcome) =
+            result.map_err(|e| crate::error::SpeedTestError::Other(e.to_string()))?;
+        if let Err(e) = outcome {
+            first_error.get_or_insert(e);
+        }
+        connections.push(stats);
     }
-
-    if let Some(handle) = progress_handle {
-        handle.abort();
+    if let Some(e) = first_error {
+        if connections.iter().all(|c| c.error.is_some()) {
+            return Err(e);
+        }
     }
 
     let elapsed = start.elapsed().as_secs_f64();
     let bytes = total_bytes.load(Ordering::Acquire);
+    let samples = monitor
+        .finish(ByteSample {
+            elapsed_secs: elapsed,
+            bytes,
+        })
+        .await;
+    let loaded_latency = probe.finish().await;
+
+    let ramp_up = ramp.map_or(ramp_up_secs(config, elapsed), |r| {
+        ramp_up_secs(config, elapsed).max(r.settled_secs)
+    });
+    let mut result = ThroughputResult::new(bytes, elapsed)
+        .with_steady_state(steady_state_bps(&samples, ramp_up))
+        .with_connections(connections);
+    result.saturating_connections = ramp.and_then(|r| r.saturating_connections);
+    result.loaded_latency = loaded_latency;
+    result.samples = throughput_samples(&samples);
 
     // Final progress update
     if let Some(cb) = progress {
         cb(ProgressUpdate {
             phase: TestPhase::Download,
-            speed_mbps: Some((bytes as f64 * 8.0) / elapsed / 1_000_000.0),
+            speed_mbps: Some(result.mbps),
             progress: 1.0,
             latency_ms: None,
         });
     }
 
-    Ok(ThroughputResult::new(bytes, elapsed))
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    use crate::mock_http;
+
+    #[tokio::test]
+    async fn test_failed_connection_keeps_partial_bytes() {
+        // The warmup and one of the two parallel downloads succeed; the
+        // other is cut off after 100 of its 1000 bytes
+        let downloads = AtomicUsize::new(0);
+        let addr = mock_http::spawn(move |line| {
+            if line.contains("bytes=1000") && downloads.fetch_add(1, Ordering::SeqCst) == 2 {
+                Some(mock_http::response(1000, &[0; 100]))
+            } else {
+                Some(mock_http::response(1000, &[0; 1000]))
+            }
+        })
+        .await;
+        let config = TestConfig {
+            parallel_connections: 2,
+            download_sizes: vec![1000],
+            latency_url: format!("http://{addr}/latency"),
+            ..TestConfig::for_server(&format!("http://{addr}"))
+        };
+
+        let client = reqwest::Client::new();
+        let result = measure_download(&client, &config, None).await.unwrap();
+        assert_eq!(result.bytes_transferred, 1100);
+        let failed: Vec<_> = result
+            .connections
+            .iter()
+            .filter(|c| c.error.is_some())
+            .collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].bytes, 100);
+    }
+
+    #[tokio::test]
+    async fn test_fails_when_every_connection_fails() {
+        // Every parallel download is cut off; only the warmup completes
+        let addr = mock_http::spawn(|line| {
+            if line.contains("bytes=1000") {
+                Some(mock_http::response(1000, &[0; 10]))
+            } else {
+                Some(mock_http::response(10, &[0; 10]))
+            }
+        })
+        .await;
+        let config = TestConfig {
+            parallel_connections: 2,
+            download_sizes: vec![10, 1000],
+            latency_url: format!("http://{addr}/latency"),
+            ..TestConfig::for_server(&format!("http://{addr}"))
+        };
+
+        let client = reqwest::Client::new();
+        assert!(measure_download(&client, &config, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_fairness_with_equal_flows() {
+        // Every connection is served at the same fixed rate, so connections
+        // opened late by the ramp-up must still come out even
+        let addr = mock_http::spawn_paced(
+            |line| {
+                let size = line
+                    .split("bytes=")
+                    .nth(1)
+                    .and_then(|rest| rest.split(' ').next())
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(0);
+                Some(mock_http::response(size, &vec![0; size]))
+            },
+            1000,
+            Duration::from_millis(10),
+        )
+        .await;
+        let config = TestConfig {
+            parallel_connections: 4,
+            adaptive_connections: true,
+            test_duration: Some(Duration::from_secs(6)),
+            download_sizes: vec![1000, 50_000],
+            latency_url: format!("http://{addr}/latency"),
+            ..TestConfig::for_server(&format!("http://{addr}"))
+        };
+
+        let client = reqwest::Client::new();
+        let result = measure_download(&client, &config, None).await.unwrap();
+        assert!(result.connections.len() > 1);
+        let fairness = result.fairness.unwrap();
+        assert!(fairness > 0.99, "fairness {fairness}");
+    }
 }
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::results::SpeedTestResult;
+
 #[derive(Error, Debug)]
 pub enum SpeedTestError {
     #[error("HTTP request failed: {0}")]
@@ -11,8 +13,12 @@ pub enum SpeedTestError {
     #[error("Invalid server response: {0}")]
     InvalidResponse(String),
 
+    /// The test was stopped early; carries the results of completed phases
     #[error("Test cancelled")]
-    Cancelled,
+    Cancelled(Box<SpeedTestResult>),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
 
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -0,0 +1,174 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, SpeedTestError};
+use crate::results::SpeedTestResult;
+
+/// Results kept before the oldest are dropped.
+pub const MAX_HISTORY: usize = 100;
+
+/// Past results stored as a JSON array in a single file, oldest first.
+///
+/// The file is rewritten through a temporary file on every change, so a
+/// crash mid-write never leaves a truncated history behind.
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+    max_entries: usize,
+}
+
+impl History {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_entries: MAX_HISTORY,
+        }
+    }
+
+    /// History stored as `history.json` in `data_dir`.
+    pub fn in_dir(data_dir: &Path) -> Self {
+        Self::new(data_dir.join("history.json"))
+    }
+
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads all stored results, oldest first. A missing file is an empty
+    /// history.
+    pub fn load(&self) -> Result<Vec<SpeedTestResult>> {
+        let data = match std::fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_str(&data).map_err(|e| {
+            SpeedTestError::Other(format!("Invalid history file {}: {e}", self.path.display()))
+        })
+    }
+
+    /// Appends results, dropping the oldest beyond the size limit, and
+    /// returns the updated history.
+    pub fn append(&self, results: &[SpeedTestResult]) -> Result<Vec<SpeedTestResult>> {
+        let mut history = self.load()?;
+        history.extend_from_slice(results);
+        if history.len() > self.max_entries {
+            let excess = history.len() - self.max_entries;
+            history.drain(0..excess);
+        }
+        self.write(&history)?;
+        Ok(history)
+    }
+
+    /// Deletes every stored result and returns how many there were, or
+    /// `None` if the file couldn't be read. An unreadable file is deleted
+    /// too, since clearing is the way out of a corrupt history.
+    pub fn clear(&self) -> Result<Option<usize>> {
+        let count = self.load().ok().map(|history| history.len());
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => Ok(count),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Some(0)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(&self, history: &[SpeedTestResult]) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(history)
+            .map_err(|e| SpeedTestError::Other(format!("Failed to serialize history: {e}")))?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Short identifier for a stored result, derived from its timestamp
+/// (e.g. `20250114-093012`).
+pub fn result_id(result: &SpeedTestResult) -> String {
+    result.timestamp.format("%Y%m%d-%H%M%S").to_string()
+}
+
+/// Finds a result by its [`result_id`], or the newest one for `latest`.
+pub fn find<'a>(history: &'a [SpeedTestResult], id: &str) -> Option<&'a SpeedTestResult> {
+    if id == "latest" {
+        return history.last();
+    }
+    // Newest first, in case two runs started within the same second
+    history.iter().rev().find(|r| result_id(r) == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn temp_history(name: &str) -> History {
+        let dir = std::env::temp_dir().join(format!("tej-history-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        History::in_dir(&dir)
+    }
+
+    fn result_at(secs: i64) -> SpeedTestResult {
+        SpeedTestResult {
+            timestamp: Utc.timestamp_opt(secs, 0).unwrap(),
+            ..SpeedTestResult::new()
+        }
+    }
+
+    #[test]
+    fn test_append_keeps_newest_entries() {
+        let history = temp_history("append").with_max_entries(2);
+        assert!(history.load().unwrap().is_empty());
+
+        history.append(&[result_at(1), result_at(2)]).unwrap();
+        history.append(&[result_at(3)]).unwrap();
+
+        let stored = history.load().unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].timestamp, result_at(2).timestamp);
+        assert_eq!(history.clear().unwrap(), Some(2));
+        assert!(history.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_by_id() {
+        let history = vec![result_at(1_736_847_012), result_at(1_736_847_100)];
+        assert_eq!(result_id(&history[0]), "20250114-093012");
+        assert_eq!(
+            find(&history, "20250114-093012").map(|r| r.timestamp),
+            Some(history[0].timestamp)
+        );
+        assert_eq!(
+            find(&history, "latest").map(|r| r.timestamp),
+            Some(history[1].timestamp)
+        );
+        assert!(find(&history, "20250101-000000").is_none());
+    }
+
+    #[test]
+    fn test_corrupt_file_is_not_overwritten() {
+        let history = temp_history("corrupt");
+        std::fs::create_dir_all(history.path().parent().unwrap()).unwrap();
+        std::fs::write(history.path(), "not json").unwrap();
+        assert!(history.append(&[result_at(1)]).is_err());
+        assert_eq!(std::fs::read_to_string(history.path()).unwrap(), "not json");
+    }
+
+    #[test]
+    fn test_clear_removes_corrupt_file() {
+        let history = temp_history("clear-corrupt");
+        std::fs::create_dir_all(history.path().parent().unwrap()).unwrap();
+        std::fs::write(history.path(), "not json").unwrap();
+        assert_eq!(history.clear().unwrap(), None);
+        assert!(!history.path().exists());
+        history.append(&[result_at(1)]).unwrap();
+        assert_eq!(history.load().unwrap().len(), 1);
+    }
+}
@@ -1,7 +1,12 @@
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
+use tokio::task::JoinHandle;
+
+use crate::backend::clone_request;
 use crate::config::TestConfig;
-use crate::error::Result;
+use crate::error::{Result, SpeedTestError};
 use crate::progress::{ProgressCallback, ProgressUpdate, TestPhase};
 use crate::results::LatencyResult;
 
@@ -9,27 +14,27 @@ pub async fn measure_latency(
     client: &reqwest::Client,
     config: &TestConfig,
     progress: Option<&ProgressCallback>,
-) -> Result<(LatencyResult, Option<String>)> {
-    let url = format!("{}?bytes=0", config.latency_url);
+) -> Result<(LatencyResult, Option<String>, Option<IpAddr>)> {
     let total_samples = config.latency_samples;
     let mut all_samples = Vec::with_capacity(total_samples);
     let mut server_location = None;
+    let mut remote_ip = None;
 
     for i in 0..total_samples {
         let start = Instant::now();
-        let resp = client.get(&url).send().await?;
+        let resp = config
+            .backend
+            .latency_request(client, config)
+            .send()
+            .await?;
         let rtt = start.elapsed().as_secs_f64() * 1000.0;
 
-        // Extract server location from cf-ray header on first response
+        // Extract server location and address from the first response
         if server_location.is_none() {
-            if let Some(ray) = resp.headers().get("cf-ray") {
-                if let Ok(ray_str) = ray.to_str() {
-                    // cf-ray format: "hex-LOCATION"
-                    if let Some(loc) = ray_str.rsplit('-').next() {
-                        server_location = Some(loc.to_string());
-                    }
-                }
-            }
+            server_location = config.backend.server_location(resp.headers());
+        }
+        if remote_ip.is_none() {
+            remote_ip = resp.remote_addr().map(|addr| addr.ip());
         }
 
         // Consume response body
@@ -54,24 +59,151 @@ pub async fn measure_latency(
         .collect();
 
     if samples.is_empty() {
-        return Err(crate::error::SpeedTestError::InvalidResponse(
+        return Err(SpeedTestError::InvalidResponse(
             "No valid latency samples after discarding warmup".to_string(),
         ));
     }
 
+    Ok((summarize_samples(samples), server_location, remote_ip))
+}
+
+/// Builds a `LatencyResult` from a non-empty set of RTT samples.
+pub(crate) fn summarize_samples(samples: Vec<f64>) -> LatencyResult {
     let min_ms = samples.iter().copied().fold(f64::INFINITY, f64::min);
     let max_ms = samples.iter().copied().fold(f64::NEG_INFINITY, f64::max);
     let avg_ms = samples.iter().sum::<f64>() / samples.len() as f64;
     let jitter_ms = crate::jitter::calculate_jitter(&samples);
+    let variance = samples.iter().map(|s| (s - avg_ms).powi(2)).sum::<f64>() / samples.len() as f64;
+
+    let mut sorted = samples.clone();
+    sorted.sort_by(f64::total_cmp);
+
+    LatencyResult {
+        min_ms,
+        avg_ms,
+        max_ms,
+        jitter_ms,
+        median_ms: percentile(&sorted, 50.0),
+        p90_ms: percentile(&sorted, 90.0),
+        p95_ms: percentile(&sorted, 95.0),
+        p99_ms: percentile(&sorted, 99.0),
+        stddev_ms: variance.sqrt(),
+        outliers: count_outliers(&sorted),
+        samples,
+    }
+}
 
-    Ok((
-        LatencyResult {
-            min_ms,
-            avg_ms,
-            max_ms,
-            jitter_ms,
-            samples,
-        },
-        server_location,
-    ))
+/// Percentile `p` (0-100) of already-sorted samples, linearly interpolating
+/// between the closest ranks.
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// Counts samples further than 1.5 x IQR below Q1 or above Q3 (Tukey's fences).
+pub fn count_outliers(sorted: &[f64]) -> usize {
+    let q1 = percentile(sorted, 25.0);
+    let q3 = percentile(sorted, 75.0);
+    let fence = 1.5 * (q3 - q1);
+    sorted
+        .iter()
+        .filter(|&&s| s < q1 - fence || s > q3 + fence)
+        .count()
+}
+
+/// Background RTT prober that runs alongside a download or upload phase
+/// to capture latency under load (bufferbloat).
+pub struct LoadedLatencyProbe {
+    samples: Arc<Mutex<Vec<f64>>>,
+    handle: JoinHandle<()>,
+}
+
+impl LoadedLatencyProbe {
+    pub fn start(client: &reqwest::Client, config: &TestConfig) -> Result<Self> {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let client = client.clone();
+        let request = config.backend.latency_request(&client, config).build()?;
+        let interval = config.loaded_latency_interval;
+        let task_samples = samples.clone();
+
+        let handle = tokio::spawn(async move {
+            loop {
+                let start = Instant::now();
+                let probe = async {
+                    let resp = client.execute(clone_request(&request)?).await?;
+                    resp.bytes().await.map_err(SpeedTestError::from)
+                };
+                // Failed probes are skipped rather than recorded
+                if probe.await.is_ok() {
+                    let rtt = start.elapsed().as_secs_f64() * 1000.0;
+                    if let Ok(mut s) = task_samples.lock() {
+                        s.push(rtt);
+                    }
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+
+        Ok(Self { samples, handle })
+    }
+
+    /// Stops probing and summarizes the samples collected so far.
+    /// Returns `None` if no probe completed while the phase was running.
+    pub async fn finish(mut self) -> Option<LatencyResult> {
+        self.handle.abort();
+        let _ = (&mut self.handle).await;
+        let samples = std::mem::take(&mut *self.samples.lock().ok()?);
+        if samples.is_empty() {
+            None
+        } else {
+            Some(summarize_samples(samples))
+        }
+    }
+}
+
+impl Drop for LoadedLatencyProbe {
+    fn drop(&mut self) {
+        // Stop probing if the phase bails out early with an error
+        self.handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile_interpolates() {
+        let sorted = [10.0, 20.0, 30.0, 40.0, 50.0];
+        assert_eq!(percentile(&sorted, 0.0), 10.0);
+        assert_eq!(percentile(&sorted, 50.0), 30.0);
+        assert_eq!(percentile(&sorted, 100.0), 50.0);
+        assert!((percentile(&sorted, 90.0) - 46.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_percentile_empty() {
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+
+    #[test]
+    fn test_outlier_skews_average_not_median() {
+        let result = summarize_samples(vec![10.0, 11.0, 10.0, 12.0, 11.0, 10.0, 250.0]);
+        assert_eq!(result.median_ms, 11.0);
+        assert!(result.avg_ms > 40.0);
+        assert_eq!(result.outliers, 1);
+        assert_eq!(result.max_ms, 250.0);
+    }
+
+    #[test]
+    fn test_stddev_constant_samples() {
+        let result = summarize_samples(vec![5.0, 5.0, 5.0]);
+        assert_eq!(result.stddev_ms, 0.0);
+        assert_eq!(result.outliers, 0);
+    }
 }
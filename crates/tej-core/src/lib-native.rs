@@ -1,20 +1,38 @@
+pub mod adaptive;
+pub mod backend;
+pub mod binding;
 pub mod config;
 pub mod data;
 pub mod download;
 pub mod error;
+pub mod history;
 pub mod jitter;
 pub mod latency;
 pub mod packet_loss;
+pub mod profile;
 pub mod progress;
 pub mod results;
 pub mod runner;
+pub mod sampler;
+pub mod timings;
+pub mod udp;
 pub mod upload;
 
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
 
-pub use config::TestConfig;
+#[cfg(test)]
+mod mock_http;
+
+pub use backend::{CloudflareBackend, SpeedTestBackend};
+pub use config::{IpVersion, TestConfig};
 pub use error::{Result, SpeedTestError};
+pub use history::History;
+pub use profile::Profile;
 pub use progress::{ProgressCallback, ProgressUpdate, TestPhase};
-pub use results::{LatencyResult, SpeedTestResult, ThroughputResult};
-pub use runner::run_speed_test;
+pub use results::{
+    AddressFamily, BufferbloatGrade, ConnectionStats, ConnectionTimings, LatencyResult,
+    PacketLossResult, PhaseError, SpeedTestResult, ThroughputResult, ThroughputSample,
+};
+pub use runner::{run_speed_test, run_speed_test_with_cancel, run_speed_tests_with_cancel};
+pub use tokio_util::sync::CancellationToken;
@@ -6,6 +6,12 @@ pub mod wasm;
 
 // Native-only modules (require tokio/reqwest)
 #[cfg(not(target_arch = "wasm32"))]
+pub mod adaptive;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod backend;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod binding;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod config;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod data;
@@ -14,31 +20,55 @@ pub mod download;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod error;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod history;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod jitter;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod latency;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod packet_loss;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod profile;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod progress;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod results;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod runner;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod sampler;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod timings;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod udp;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod upload;
 
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod mock_http;
+
 // Re-exports for native builds
 #[cfg(not(target_arch = "wasm32"))]
-pub use config::TestConfig;
+pub use backend::{CloudflareBackend, SpeedTestBackend};
+#[cfg(not(target_arch = "wasm32"))]
+pub use config::{IpVersion, TestConfig};
 #[cfg(not(target_arch = "wasm32"))]
 pub use error::{Result, SpeedTestError};
 #[cfg(not(target_arch = "wasm32"))]
+pub use history::History;
+#[cfg(not(target_arch = "wasm32"))]
+pub use profile::Profile;
+#[cfg(not(target_arch = "wasm32"))]
 pub use progress::{ProgressCallback, ProgressUpdate, TestPhase};
 #[cfg(not(target_arch = "wasm32"))]
-pub use results::{LatencyResult, SpeedTestResult, ThroughputResult};
+pub use results::{
+    AddressFamily, BufferbloatGrade, ConnectionStats, ConnectionTimings, LatencyResult,
+    PacketLossResult, PhaseError, SpeedTestResult, ThroughputResult, ThroughputSample,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use runner::{run_speed_test, run_speed_test_with_cancel, run_speed_tests_with_cancel};
 #[cfg(not(target_arch = "wasm32"))]
-pub use runner::run_speed_test;
+pub use tokio_util::sync::CancellationToken;
 
 // Re-export WASM function for WASM builds
 #[cfg(target_arch = "wasm32")]
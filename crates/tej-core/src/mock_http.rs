@@ -0,0 +1,82 @@
+//! Bare-bones HTTP/1.1 server for tests that need an endpoint to misbehave
+//! in ways `tej-server` never would.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Serves one request per connection on a local port. `respond` gets the
+/// request line (`GET /path HTTP/1.1`) and returns the raw response bytes,
+/// or `None` to close the connection without answering.
+pub async fn spawn<F>(respond: F) -> SocketAddr
+where
+    F: Fn(&str) -> Option<Vec<u8>> + Send + Sync + 'static,
+{
+    serve(respond, None).await
+}
+
+/// Like [`spawn`], but writes each response `chunk` bytes at a time with
+/// `interval` between writes, so every connection gets the same fixed rate.
+pub async fn spawn_paced<F>(respond: F, chunk: usize, interval: Duration) -> SocketAddr
+where
+    F: Fn(&str) -> Option<Vec<u8>> + Send + Sync + 'static,
+{
+    serve(respond, Some((chunk, interval))).await
+}
+
+async fn serve<F>(respond: F, pace: Option<(usize, Duration)>) -> SocketAddr
+where
+    F: Fn(&str) -> Option<Vec<u8>> + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let respond = Arc::new(respond);
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let respond = respond.clone();
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let line = request.lines().next().unwrap_or_default();
+                let Some(response) = respond(line) else {
+                    return;
+                };
+                match pace {
+                    None => {
+                        let _ = socket.write_all(&response).await;
+                    }
+                    Some((chunk, interval)) => {
+                        for piece in response.chunks(chunk) {
+                            if socket.write_all(piece).await.is_err() {
+                                return;
+                            }
+                            tokio::time::sleep(interval).await;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// A `200 OK` response that claims `content_length` bytes but carries
+/// `body`, which may be shorter to simulate a connection cut mid-transfer.
+pub fn response(content_length: usize, body: &[u8]) -> Vec<u8> {
+    let mut response =
+        format!("HTTP/1.1 200 OK\r\nContent-Length: {content_length}\r\nConnection: close\r\n\r\n")
+            .into_bytes();
+    response.extend_from_slice(body);
+    response
+}
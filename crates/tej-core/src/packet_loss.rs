@@ -1,33 +1,32 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::config::TestConfig;
 use crate::error::Result;
 use crate::progress::{ProgressCallback, ProgressUpdate, TestPhase};
+use crate::results::PacketLossResult;
 
 pub async fn measure_packet_loss(
     client: &reqwest::Client,
     config: &TestConfig,
     progress: Option<&ProgressCallback>,
-) -> Result<f64> {
-    let url = format!("{}?bytes=0", config.latency_url);
+) -> Result<PacketLossResult> {
     let count = config.packet_loss_count;
     let timeout = config.packet_loss_timeout;
-    let mut failures = 0u32;
+    let mut rtt_ms = Vec::with_capacity(count);
 
     for i in 0..count {
-        let result = tokio::time::timeout(timeout, client.get(&url).send()).await;
+        let request = config.backend.latency_request(client, config);
+        let start = Instant::now();
+        let result = tokio::time::timeout(timeout, request.send()).await;
 
-        match result {
-            Ok(Ok(resp)) => {
-                // Also count HTTP errors as failures
-                if !resp.status().is_success() {
-                    failures += 1;
-                }
+        // HTTP errors count as lost probes too
+        let rtt = match result {
+            Ok(Ok(resp)) if resp.status().is_success() => {
+                Some(start.elapsed().as_secs_f64() * 1000.0)
             }
-            _ => {
-                failures += 1;
-            }
-        }
+            _ => None,
+        };
+        rtt_ms.push(rtt);
 
         // Small delay between requests to avoid bursting
         tokio::time::sleep(Duration::from_millis(50)).await;
@@ -42,5 +41,6 @@ pub async fn measure_packet_loss(
         }
     }
 
-    Ok(failures as f64 / count as f64 * 100.0)
+    // Requests are sequential, so they can't be reordered or duplicated
+    Ok(PacketLossResult::new(rtt_ms, 0, 0))
 }
@@ -0,0 +1,127 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::TestConfig;
+
+/// Built-in test presets trading run time for measurement depth.
+///
+/// A profile only sets the knobs it tunes (transfer sizes, sample counts,
+/// phase duration and connection count) and leaves everything else, such as
+/// server URLs, as configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    /// About five seconds: short timed transfers and a handful of probes
+    Quick,
+    /// The configured settings as they are, so selecting it is the same as
+    /// selecting no profile
+    #[default]
+    Standard,
+    /// Long timed transfers, more connections and many latency and loss probes
+    Thorough,
+}
+
+impl Profile {
+    pub const ALL: [Profile; 3] = [Profile::Quick, Profile::Standard, Profile::Thorough];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::Quick => "quick",
+            Profile::Standard => "standard",
+            Profile::Thorough => "thorough",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Profile::Quick => "~5 s sanity check",
+            Profile::Standard => "Default balance of speed and accuracy",
+            Profile::Thorough => "Long run for diagnosing a line",
+        }
+    }
+
+    /// Returns `config` with this profile's settings applied.
+    pub fn apply(self, config: TestConfig) -> TestConfig {
+        match self {
+            Profile::Quick => TestConfig {
+                download_sizes: vec![100_000, 1_000_000, 5_000_000, 10_000_000],
+                upload_size: 2_000_000,
+                latency_samples: 8,
+                latency_warmup: 2,
+                packet_loss_count: 5,
+                parallel_connections: 4,
+                test_duration: Some(Duration::from_secs(2)),
+                ramp_up: Duration::from_millis(500),
+                ..config
+            },
+            Profile::Standard => config,
+            Profile::Thorough => TestConfig {
+                download_sizes: vec![100_000, 10_000_000, 25_000_000, 100_000_000],
+                upload_size: 25_000_000,
+                latency_samples: 50,
+                latency_warmup: 5,
+                packet_loss_count: 100,
+                parallel_connections: 8,
+                test_duration: Some(Duration::from_secs(15)),
+                ramp_up: Duration::from_secs(2),
+                ..config
+            },
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Profile::ALL
+            .into_iter()
+            .find(|p| p.name() == s)
+            .ok_or_else(|| format!("Unknown profile: {s}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_names_roundtrip() {
+        for profile in Profile::ALL {
+            assert_eq!(profile.name().parse::<Profile>(), Ok(profile));
+        }
+        assert!("turbo".parse::<Profile>().is_err());
+    }
+
+    #[test]
+    fn test_profile_keeps_untuned_settings() {
+        let config = TestConfig::for_server("http://10.0.0.5:8080");
+        let quick = Profile::Quick.apply(config.clone());
+        assert_eq!(quick.download_url, config.download_url);
+        assert_eq!(quick.test_duration, Some(Duration::from_secs(2)));
+        assert!(quick.latency_warmup < quick.latency_samples);
+    }
+
+    #[test]
+    fn test_standard_keeps_configured_settings() {
+        // Values from a config file survive, as with no profile at all
+        let configured = TestConfig {
+            parallel_connections: 12,
+            test_duration: Some(Duration::from_secs(8)),
+            ..TestConfig::default()
+        };
+        let standard = Profile::Standard.apply(configured.clone());
+        assert_eq!(standard.parallel_connections, 12);
+        assert_eq!(standard.test_duration, configured.test_duration);
+        assert_eq!(standard.download_sizes, configured.download_sizes);
+    }
+}
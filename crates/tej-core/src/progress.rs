@@ -1,14 +1,42 @@
 use std::sync::Arc;
 
-#[derive(Debug, Clone, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TestPhase {
+    Connection,
     Latency,
     Download,
     Upload,
+    /// Single-connection comparison run after the download phase
+    SingleStreamDownload,
+    /// Single-connection comparison run after the upload phase
+    SingleStreamUpload,
+    /// HTTP probe loss
     PacketLoss,
+    /// Datagram loss against a UDP echo server
+    UdpPacketLoss,
     Done,
 }
 
+impl TestPhase {
+    /// Snake-case name, as used in serialized results.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TestPhase::Connection => "connection",
+            TestPhase::Latency => "latency",
+            TestPhase::Download => "download",
+            TestPhase::Upload => "upload",
+            TestPhase::SingleStreamDownload => "single_stream_download",
+            TestPhase::SingleStreamUpload => "single_stream_upload",
+            TestPhase::PacketLoss => "packet_loss",
+            TestPhase::UdpPacketLoss => "udp_packet_loss",
+            TestPhase::Done => "done",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ProgressUpdate {
     pub phase: TestPhase,
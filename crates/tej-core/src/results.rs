@@ -1,14 +1,183 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::progress::TestPhase;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpeedTestResult {
     pub timestamp: DateTime<Utc>,
     pub server_location: Option<String>,
+    /// Server address the test connections used
+    pub remote_ip: Option<IpAddr>,
+    /// Address family of `remote_ip`
+    pub address_family: Option<AddressFamily>,
+    /// Network interface the test was bound to, if any
+    pub interface: Option<String>,
+    /// Local address the test was bound to, if any
+    pub source_address: Option<IpAddr>,
+    /// Setup cost of a fresh connection to the test server
+    pub connection: Option<ConnectionTimings>,
     pub latency: Option<LatencyResult>,
     pub download: Option<ThroughputResult>,
     pub upload: Option<ThroughputResult>,
-    pub packet_loss: Option<f64>,
+    /// Download over a single connection, with `TestConfig::compare_single_stream`
+    #[serde(default)]
+    pub single_stream_download: Option<ThroughputResult>,
+    /// Upload over a single connection, with `TestConfig::compare_single_stream`
+    #[serde(default)]
+    pub single_stream_upload: Option<ThroughputResult>,
+    /// Failed request probes against the latency endpoint. Also accepts the
+    /// bare percentage stored by earlier versions.
+    #[serde(default, deserialize_with = "deserialize_packet_loss")]
+    pub packet_loss: Option<PacketLossResult>,
+    /// Datagram loss measured with sequenced UDP probes to an echo server
+    pub udp_packet_loss: Option<PacketLossResult>,
+    /// Grade for latency increase under load, derived from idle vs loaded latency
+    pub bufferbloat: Option<BufferbloatGrade>,
+    /// Phases that failed when running with `TestConfig::continue_on_error`
+    #[serde(default)]
+    pub errors: Vec<PhaseError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PacketLossResult {
+    /// Probes sent
+    pub sent: u32,
+    /// Distinct probes answered
+    pub received: u32,
+    /// Percentage of probes never answered
+    pub loss_percent: f64,
+    /// Probes that arrived after a later probe
+    pub out_of_order: u32,
+    /// Extra copies of probes that were already received
+    pub duplicates: u32,
+    /// Round-trip time of each probe in milliseconds, by sequence number
+    /// (`None` for lost probes)
+    pub rtt_ms: Vec<Option<f64>>,
+}
+
+impl PacketLossResult {
+    /// Builds a result from per-probe RTTs, deriving the received count and
+    /// loss percentage.
+    pub fn new(rtt_ms: Vec<Option<f64>>, out_of_order: u32, duplicates: u32) -> Self {
+        let sent = rtt_ms.len() as u32;
+        let received = rtt_ms.iter().filter(|rtt| rtt.is_some()).count() as u32;
+        let loss_percent = if sent == 0 {
+            0.0
+        } else {
+            (sent - received) as f64 / sent as f64 * 100.0
+        };
+        Self {
+            sent,
+            received,
+            loss_percent,
+            out_of_order,
+            duplicates,
+            rtt_ms,
+        }
+    }
+
+    /// Mean RTT of the probes that were answered.
+    pub fn avg_rtt_ms(&self) -> Option<f64> {
+        let rtts: Vec<f64> = self.rtt_ms.iter().flatten().copied().collect();
+        if rtts.is_empty() {
+            return None;
+        }
+        Some(rtts.iter().sum::<f64>() / rtts.len() as f64)
+    }
+}
+
+/// Reads `packet_loss` either as a `PacketLossResult` or as the plain
+/// percentage earlier versions serialized, so saved history keeps loading.
+fn deserialize_packet_loss<'de, D>(deserializer: D) -> Result<Option<PacketLossResult>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Percent(f64),
+        Full(PacketLossResult),
+    }
+
+    Ok(
+        Option::<Stored>::deserialize(deserializer)?.map(|stored| match stored {
+            Stored::Full(result) => result,
+            Stored::Percent(loss_percent) => PacketLossResult {
+                loss_percent,
+                ..PacketLossResult::new(Vec::new(), 0, 0)
+            },
+        }),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    pub fn of(ip: &IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => AddressFamily::Ipv4,
+            IpAddr::V6(_) => AddressFamily::Ipv6,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AddressFamily::Ipv4 => "IPv4",
+            AddressFamily::Ipv6 => "IPv6",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseError {
+    pub phase: TestPhase,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionTimings {
+    /// Address the server name resolved to
+    pub remote_ip: IpAddr,
+    /// DNS lookup time in milliseconds
+    pub dns_ms: f64,
+    /// TCP connect time in milliseconds
+    pub tcp_connect_ms: f64,
+    /// TLS handshake time in milliseconds (`None` for plain HTTP)
+    pub tls_handshake_ms: Option<f64>,
+    /// Time from sending the request to the first response byte in milliseconds
+    pub ttfb_ms: f64,
+    /// Sum of all stages in milliseconds
+    pub total_ms: f64,
+}
+
+impl ConnectionTimings {
+    pub fn new(
+        remote_ip: IpAddr,
+        dns: Duration,
+        tcp_connect: Duration,
+        tls_handshake: Option<Duration>,
+        ttfb: Duration,
+    ) -> Self {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let total = dns + tcp_connect + tls_handshake.unwrap_or_default() + ttfb;
+        Self {
+            remote_ip,
+            dns_ms: ms(dns),
+            tcp_connect_ms: ms(tcp_connect),
+            tls_handshake_ms: tls_handshake.map(ms),
+            ttfb_ms: ms(ttfb),
+            total_ms: ms(total),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,20 +190,144 @@ pub struct LatencyResult {
     pub max_ms: f64,
     /// Jitter in milliseconds (mean absolute difference between consecutive samples)
     pub jitter_ms: f64,
+    /// Median RTT in milliseconds
+    #[serde(default)]
+    pub median_ms: f64,
+    /// 90th percentile RTT in milliseconds
+    #[serde(default)]
+    pub p90_ms: f64,
+    /// 95th percentile RTT in milliseconds
+    #[serde(default)]
+    pub p95_ms: f64,
+    /// 99th percentile RTT in milliseconds
+    #[serde(default)]
+    pub p99_ms: f64,
+    /// Population standard deviation of RTT in milliseconds
+    #[serde(default)]
+    pub stddev_ms: f64,
+    /// Samples outside 1.5 x IQR of the quartiles
+    #[serde(default)]
+    pub outliers: usize,
     /// All RTT samples in milliseconds
     pub samples: Vec<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThroughputResult {
-    /// Speed in bits per second
+    /// Speed in bits per second, measured over the steady-state window when available
     pub bps: f64,
-    /// Speed in megabits per second
+    /// Speed in megabits per second, measured over the steady-state window when available
     pub mbps: f64,
+    /// Speed in bits per second over the whole transfer, including ramp-up
+    #[serde(default)]
+    pub raw_bps: f64,
+    /// Speed in megabits per second over the whole transfer, including ramp-up
+    #[serde(default)]
+    pub raw_mbps: f64,
     /// Total bytes transferred
     pub bytes_transferred: u64,
     /// Duration of the test in seconds
     pub duration_secs: f64,
+    /// RTT measured while this transfer was saturating the link
+    pub loaded_latency: Option<LatencyResult>,
+    /// Throughput over time, sampled while the transfer was running
+    #[serde(default)]
+    pub samples: Vec<ThroughputSample>,
+    /// Bytes and speed of each parallel connection
+    #[serde(default)]
+    pub connections: Vec<ConnectionStats>,
+    /// Jain's fairness index over per-connection speeds: 1.0 when every
+    /// connection got an equal share, approaching 1/n when one dominates
+    #[serde(default)]
+    pub fairness: Option<f64>,
+    /// In adaptive mode, how many connections it took for throughput to stop
+    /// improving (`None` if it kept improving up to the connection limit)
+    #[serde(default)]
+    pub saturating_connections: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionStats {
+    /// Position of the connection among the parallel transfers
+    pub index: usize,
+    /// Bytes this connection transferred
+    pub bytes: u64,
+    /// Seconds from the start of the phase until this connection finished
+    pub duration_secs: f64,
+    /// Average speed of this connection in megabits per second
+    pub mbps: f64,
+    /// Why this connection stopped early, if it failed
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl ConnectionStats {
+    pub fn new(index: usize, bytes: u64, duration_secs: f64) -> Self {
+        let mbps = if duration_secs > 0.0 {
+            (bytes as f64 * 8.0) / duration_secs / 1_000_000.0
+        } else {
+            0.0
+        };
+        Self {
+            index,
+            bytes,
+            duration_secs,
+            mbps,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThroughputSample {
+    /// Seconds since the transfer started
+    pub elapsed_secs: f64,
+    /// Total bytes transferred so far
+    pub bytes: u64,
+    /// Speed in megabits per second since the previous sample
+    pub mbps: f64,
+}
+
+/// Bufferbloat grade based on how much average latency rises under load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BufferbloatGrade {
+    #[serde(rename = "A+")]
+    APlus,
+    A,
+    B,
+    C,
+    D,
+    F,
+}
+
+impl BufferbloatGrade {
+    /// Grades a latency increase (loaded avg minus idle avg) in milliseconds.
+    pub fn from_latency_increase(increase_ms: f64) -> Self {
+        if increase_ms < 5.0 {
+            Self::APlus
+        } else if increase_ms < 30.0 {
+            Self::A
+        } else if increase_ms < 60.0 {
+            Self::B
+        } else if increase_ms < 200.0 {
+            Self::C
+        } else if increase_ms < 400.0 {
+            Self::D
+        } else {
+            Self::F
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::APlus => "A+",
+            Self::A => "A",
+            Self::B => "B",
+            Self::C => "C",
+            Self::D => "D",
+            Self::F => "F",
+        }
+    }
 }
 
 impl ThroughputResult {
@@ -47,10 +340,61 @@ impl ThroughputResult {
         Self {
             bps,
             mbps: bps / 1_000_000.0,
+            raw_bps: bps,
+            raw_mbps: bps / 1_000_000.0,
             bytes_transferred: bytes,
             duration_secs,
+            loaded_latency: None,
+            samples: Vec::new(),
+            connections: Vec::new(),
+            fairness: None,
+            saturating_connections: None,
+        }
+    }
+
+    /// Attaches per-connection stats and the fairness index derived from them.
+    pub fn with_connections(mut self, mut connections: Vec<ConnectionStats>) -> Self {
+        connections.sort_by_key(|c| c.index);
+        let speeds: Vec<f64> = connections.iter().map(|c| c.mbps).collect();
+        self.fairness = jain_fairness(&speeds);
+        self.connections = connections;
+        self
+    }
+
+    /// Slowest and fastest connection speeds in megabits per second.
+    pub fn connection_speed_range(&self) -> Option<(f64, f64)> {
+        let speeds = self.connections.iter().map(|c| c.mbps);
+        let min = speeds.clone().reduce(f64::min)?;
+        let max = speeds.reduce(f64::max)?;
+        Some((min, max))
+    }
+
+    /// How many times faster this result is than `single_stream`. Values well
+    /// above 1 point to per-flow shaping or a high bandwidth-delay product.
+    pub fn speedup_over(&self, single_stream: &ThroughputResult) -> Option<f64> {
+        (single_stream.mbps > 0.0).then(|| self.mbps / single_stream.mbps)
+    }
+
+    /// Replaces the headline speed with a steady-state measurement,
+    /// keeping the whole-transfer figure in `raw_bps`/`raw_mbps`.
+    pub fn with_steady_state(mut self, steady_state_bps: Option<f64>) -> Self {
+        if let Some(bps) = steady_state_bps {
+            self.bps = bps;
+            self.mbps = bps / 1_000_000.0;
         }
+        self
+    }
+}
+
+/// Jain's fairness index: `(sum x)^2 / (n * sum x^2)`. `None` without
+/// any traffic to compare.
+pub fn jain_fairness(values: &[f64]) -> Option<f64> {
+    let sum: f64 = values.iter().sum();
+    let sum_sq: f64 = values.iter().map(|x| x * x).sum();
+    if values.is_empty() || sum_sq <= 0.0 {
+        return None;
     }
+    Some(sum * sum / (values.len() as f64 * sum_sq))
 }
 
 impl SpeedTestResult {
@@ -58,12 +402,42 @@ impl SpeedTestResult {
         Self {
             timestamp: Utc::now(),
             server_location: None,
+            remote_ip: None,
+            address_family: None,
+            interface: None,
+            source_address: None,
+            connection: None,
             latency: None,
             download: None,
             upload: None,
+            single_stream_download: None,
+            single_stream_upload: None,
             packet_loss: None,
+            udp_packet_loss: None,
+            bufferbloat: None,
+            errors: Vec::new(),
         }
     }
+
+    /// Error message for `phase`, if it failed.
+    pub fn phase_error(&self, phase: TestPhase) -> Option<&str> {
+        self.errors
+            .iter()
+            .find(|e| e.phase == phase)
+            .map(|e| e.error.as_str())
+    }
+
+    /// Worst-case increase in average latency between the idle measurement
+    /// and the download/upload phases, in milliseconds.
+    pub fn loaded_latency_increase_ms(&self) -> Option<f64> {
+        let idle = self.latency.as_ref()?.avg_ms;
+        [&self.download, &self.upload]
+            .into_iter()
+            .flatten()
+            .filter_map(|t| t.loaded_latency.as_ref())
+            .map(|l| (l.avg_ms - idle).max(0.0))
+            .reduce(f64::max)
+    }
 }
 
 impl Default for SpeedTestResult {
@@ -103,25 +477,194 @@ mod tests {
         assert_eq!(r.bps, 0.0);
     }
 
+    #[test]
+    fn test_throughput_steady_state_keeps_raw() {
+        let r = ThroughputResult::new(1_000_000, 1.0).with_steady_state(Some(16_000_000.0));
+        assert!((r.mbps - 16.0).abs() < 0.01);
+        assert!((r.raw_mbps - 8.0).abs() < 0.01);
+
+        let r = ThroughputResult::new(1_000_000, 1.0).with_steady_state(None);
+        assert!((r.mbps - r.raw_mbps).abs() < 0.01);
+    }
+
     #[test]
     fn test_result_serialization_roundtrip() {
         let result = SpeedTestResult {
             timestamp: Utc::now(),
             server_location: Some("SFO".to_string()),
+            remote_ip: Some(IpAddr::from([104, 16, 0, 1])),
+            address_family: Some(AddressFamily::Ipv4),
+            interface: Some("eth0".to_string()),
+            source_address: None,
+            connection: Some(ConnectionTimings::new(
+                IpAddr::from([104, 16, 0, 1]),
+                Duration::from_millis(4),
+                Duration::from_millis(10),
+                Some(Duration::from_millis(20)),
+                Duration::from_millis(15),
+            )),
             latency: Some(LatencyResult {
                 min_ms: 5.0,
                 avg_ms: 10.0,
                 max_ms: 15.0,
                 jitter_ms: 2.0,
+                median_ms: 10.0,
+                p90_ms: 14.0,
+                p95_ms: 14.5,
+                p99_ms: 14.9,
+                stddev_ms: 4.08,
+                outliers: 0,
                 samples: vec![5.0, 10.0, 15.0],
             }),
             download: Some(ThroughputResult::new(10_000_000, 2.0)),
             upload: Some(ThroughputResult::new(5_000_000, 2.0)),
-            packet_loss: Some(0.0),
+            single_stream_download: None,
+            single_stream_upload: None,
+            packet_loss: Some(PacketLossResult::new(vec![Some(12.0), None], 0, 0)),
+            udp_packet_loss: None,
+            bufferbloat: Some(BufferbloatGrade::APlus),
+            errors: vec![PhaseError {
+                phase: TestPhase::PacketLoss,
+                error: "timed out".to_string(),
+            }],
         };
         let json = serde_json::to_string(&result).unwrap();
         let deserialized: SpeedTestResult = serde_json::from_str(&json).unwrap();
         assert_eq!(deserialized.server_location, Some("SFO".to_string()));
-        assert!((deserialized.download.unwrap().mbps - 40.0).abs() < 0.01);
+        assert!((deserialized.download.as_ref().unwrap().mbps - 40.0).abs() < 0.01);
+        assert_eq!(deserialized.bufferbloat, Some(BufferbloatGrade::APlus));
+        assert_eq!(deserialized.address_family, Some(AddressFamily::Ipv4));
+        assert!(json.contains(r#""address_family":"ipv4""#));
+        let loss = deserialized.packet_loss.as_ref().unwrap();
+        assert_eq!(loss.rtt_ms, vec![Some(12.0), None]);
+        assert!((loss.loss_percent - 50.0).abs() < 1e-9);
+        assert_eq!(
+            deserialized.phase_error(TestPhase::PacketLoss),
+            Some("timed out")
+        );
+        assert_eq!(deserialized.phase_error(TestPhase::Upload), None);
+        let connection = deserialized.connection.unwrap();
+        assert_eq!(connection.remote_ip, IpAddr::from([104, 16, 0, 1]));
+        assert!((connection.total_ms - 49.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_connection_timings_without_tls() {
+        let t = ConnectionTimings::new(
+            IpAddr::from([127, 0, 0, 1]),
+            Duration::from_millis(1),
+            Duration::from_millis(2),
+            None,
+            Duration::from_millis(3),
+        );
+        assert_eq!(t.tls_handshake_ms, None);
+        assert!((t.total_ms - 6.0).abs() < 0.01);
+    }
+
+    fn latency(avg_ms: f64) -> LatencyResult {
+        LatencyResult {
+            min_ms: avg_ms,
+            avg_ms,
+            max_ms: avg_ms,
+            jitter_ms: 0.0,
+            median_ms: avg_ms,
+            p90_ms: avg_ms,
+            p95_ms: avg_ms,
+            p99_ms: avg_ms,
+            stddev_ms: 0.0,
+            outliers: 0,
+            samples: vec![avg_ms],
+        }
+    }
+
+    #[test]
+    fn test_bufferbloat_grade_thresholds() {
+        assert_eq!(
+            BufferbloatGrade::from_latency_increase(0.0),
+            BufferbloatGrade::APlus
+        );
+        assert_eq!(
+            BufferbloatGrade::from_latency_increase(29.9),
+            BufferbloatGrade::A
+        );
+        assert_eq!(
+            BufferbloatGrade::from_latency_increase(100.0),
+            BufferbloatGrade::C
+        );
+        assert_eq!(
+            BufferbloatGrade::from_latency_increase(1000.0),
+            BufferbloatGrade::F
+        );
+    }
+
+    #[test]
+    fn test_loaded_latency_increase_uses_worst_phase() {
+        let mut result = SpeedTestResult::new();
+        result.latency = Some(latency(10.0));
+        let mut download = ThroughputResult::new(1_000_000, 1.0);
+        download.loaded_latency = Some(latency(40.0));
+        let mut upload = ThroughputResult::new(1_000_000, 1.0);
+        upload.loaded_latency = Some(latency(90.0));
+        result.download = Some(download);
+        result.upload = Some(upload);
+        assert_eq!(result.loaded_latency_increase_ms(), Some(80.0));
+    }
+
+    #[test]
+    fn test_loaded_latency_increase_without_probes() {
+        let mut result = SpeedTestResult::new();
+        result.latency = Some(latency(10.0));
+        result.download = Some(ThroughputResult::new(1_000_000, 1.0));
+        assert_eq!(result.loaded_latency_increase_ms(), None);
+    }
+
+    #[test]
+    fn test_packet_loss_result_counts() {
+        let loss = PacketLossResult::new(vec![Some(10.0), None, Some(20.0), None], 1, 2);
+        assert_eq!(loss.sent, 4);
+        assert_eq!(loss.received, 2);
+        assert!((loss.loss_percent - 50.0).abs() < 1e-9);
+        assert_eq!(loss.avg_rtt_ms(), Some(15.0));
+        assert_eq!(PacketLossResult::new(vec![], 0, 0).loss_percent, 0.0);
+        assert_eq!(PacketLossResult::new(vec![None], 0, 0).avg_rtt_ms(), None);
+    }
+
+    #[test]
+    fn test_packet_loss_reads_legacy_percentage() {
+        let mut value = serde_json::to_value(SpeedTestResult::new()).unwrap();
+        value["packet_loss"] = serde_json::json!(5.0);
+        let result: SpeedTestResult = serde_json::from_value(value).unwrap();
+        let loss = result.packet_loss.unwrap();
+        assert_eq!(loss.loss_percent, 5.0);
+        assert!(loss.rtt_ms.is_empty());
+    }
+
+    #[test]
+    fn test_jain_fairness() {
+        assert!((jain_fairness(&[10.0, 10.0, 10.0]).unwrap() - 1.0).abs() < 1e-9);
+        // One flow carrying everything out of four
+        assert!((jain_fairness(&[40.0, 0.0, 0.0, 0.0]).unwrap() - 0.25).abs() < 1e-9);
+        assert_eq!(jain_fairness(&[]), None);
+        assert_eq!(jain_fairness(&[0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn test_with_connections_sorts_and_scores() {
+        let result = ThroughputResult::new(3_000_000, 1.0).with_connections(vec![
+            ConnectionStats::new(1, 1_000_000, 1.0),
+            ConnectionStats::new(0, 2_000_000, 1.0),
+        ]);
+        assert_eq!(result.connections[0].index, 0);
+        assert!((result.connections[0].mbps - 16.0).abs() < 1e-9);
+        assert_eq!(result.connection_speed_range(), Some((8.0, 16.0)));
+        assert!((result.fairness.unwrap() - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_speedup_over_single_stream() {
+        let multi = ThroughputResult::new(12_000_000, 1.0);
+        let single = ThroughputResult::new(3_000_000, 1.0);
+        assert!((multi.speedup_over(&single).unwrap() - 4.0).abs() < 1e-9);
+        assert_eq!(multi.speedup_over(&ThroughputResult::new(0, 1.0)), None);
     }
 }
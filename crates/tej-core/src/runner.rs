@@ -1,51 +1,171 @@
+use std::future::Future;
 use std::time::Duration;
 
+use tokio_util::sync::CancellationToken;
+
+use crate::binding;
 use crate::config::TestConfig;
 use crate::download::measure_download;
-use crate::error::Result;
+use crate::error::{Result, SpeedTestError};
 use crate::latency::measure_latency;
 use crate::packet_loss::measure_packet_loss;
 use crate::progress::{ProgressCallback, ProgressUpdate, TestPhase};
-use crate::results::SpeedTestResult;
+use crate::results::{AddressFamily, BufferbloatGrade, PhaseError, SpeedTestResult};
+use crate::timings::measure_connection_timings;
+use crate::udp::measure_udp_loss;
 use crate::upload::measure_upload;
 
 pub async fn run_speed_test(
     config: &TestConfig,
     progress: Option<ProgressCallback>,
 ) -> Result<SpeedTestResult> {
-    let client = reqwest::Client::builder()
-        .timeout(config.timeout)
-        .pool_max_idle_per_host(config.parallel_connections)
-        .tcp_nodelay(true)
-        .no_gzip()
-        .no_brotli()
-        .no_deflate()
-        .connect_timeout(Duration::from_secs(10))
-        .build()?;
+    run_speed_test_with_cancel(config, progress, CancellationToken::new()).await
+}
+
+/// Like [`run_speed_test`], but stops as soon as `cancel` is triggered.
+///
+/// The running phase is dropped, which aborts its in-flight transfers and
+/// progress tasks, and `SpeedTestError::Cancelled` is returned carrying the
+/// results of the phases that had already completed.
+pub async fn run_speed_test_with_cancel(
+    config: &TestConfig,
+    progress: Option<ProgressCallback>,
+    cancel: CancellationToken,
+) -> Result<SpeedTestResult> {
+    config.validate()?;
+    let client = build_client(config)?;
 
     let mut result = SpeedTestResult::new();
+    result.interface = config.interface.clone();
+    result.source_address = config.source_address;
     let progress_ref = progress.as_ref();
 
+    // Connection setup breakdown on a fresh, unpooled connection
+    if let Some(cb) = progress_ref {
+        cb(ProgressUpdate {
+            phase: TestPhase::Connection,
+            speed_mbps: None,
+            progress: 0.0,
+            latency_ms: None,
+        });
+    }
+    // Best-effort even without continue_on_error: the probe opens its own
+    // connection outside the client, so a failure here says nothing about
+    // whether the remaining phases can run
+    result.connection = match until_cancelled(
+        &cancel,
+        &result,
+        measure_connection_timings(&client, config),
+    )
+    .await
+    {
+        Ok(timings) => Some(timings),
+        Err(e @ SpeedTestError::Cancelled(_)) => return Err(e),
+        Err(e) => {
+            result.errors.push(PhaseError {
+                phase: TestPhase::Connection,
+                error: e.to_string(),
+            });
+            None
+        }
+    };
+
     // Phase 1: Latency + Jitter
-    let (latency_result, server_location) = measure_latency(&client, config, progress_ref).await?;
-    result.latency = Some(latency_result);
-    result.server_location = server_location;
+    if let Some((latency_result, server_location, remote_ip)) = run_phase(
+        config,
+        &cancel,
+        &mut result,
+        TestPhase::Latency,
+        measure_latency(&client, config, progress_ref),
+    )
+    .await?
+    {
+        result.latency = Some(latency_result);
+        result.server_location = server_location;
+        result.remote_ip = remote_ip;
+    }
+    // Fall back to the address the connection probe resolved
+    result.remote_ip = result
+        .remote_ip
+        .or(result.connection.as_ref().map(|c| c.remote_ip));
+    result.address_family = result
+        .remote_ip
+        .as_ref()
+        .map(AddressFamily::of)
+        .or(config.ip_version.family());
 
-    // Phase 2: Download
+    // Phase 2: Download, then again over one connection when comparing
+    let single_stream = config.single_stream();
     if !config.skip_download {
-        let download_result = measure_download(&client, config, progress_ref).await?;
-        result.download = Some(download_result);
+        result.download = run_phase(
+            config,
+            &cancel,
+            &mut result,
+            TestPhase::Download,
+            measure_download(&client, config, progress_ref),
+        )
+        .await?;
+
+        if config.compare_single_stream {
+            result.single_stream_download = run_phase(
+                config,
+                &cancel,
+                &mut result,
+                TestPhase::SingleStreamDownload,
+                measure_download(&client, &single_stream, progress_ref),
+            )
+            .await?;
+        }
     }
 
-    // Phase 3: Upload
+    // Phase 3: Upload, likewise
     if !config.skip_upload {
-        let upload_result = measure_upload(&client, config, progress_ref).await?;
-        result.upload = Some(upload_result);
+        result.upload = run_phase(
+            config,
+            &cancel,
+            &mut result,
+            TestPhase::Upload,
+            measure_upload(&client, config, progress_ref),
+        )
+        .await?;
+
+        if config.compare_single_stream {
+            result.single_stream_upload = run_phase(
+                config,
+                &cancel,
+                &mut result,
+                TestPhase::SingleStreamUpload,
+                measure_upload(&client, &single_stream, progress_ref),
+            )
+            .await?;
+        }
     }
 
     // Phase 4: Packet Loss
-    let packet_loss = measure_packet_loss(&client, config, progress_ref).await?;
-    result.packet_loss = Some(packet_loss);
+    result.packet_loss = run_phase(
+        config,
+        &cancel,
+        &mut result,
+        TestPhase::PacketLoss,
+        measure_packet_loss(&client, config, progress_ref),
+    )
+    .await?;
+
+    // Phase 5: UDP datagram loss, when an echo endpoint is available
+    if config.udp_echo_addr.is_some() {
+        result.udp_packet_loss = run_phase(
+            config,
+            &cancel,
+            &mut result,
+            TestPhase::UdpPacketLoss,
+            measure_udp_loss(config, progress_ref),
+        )
+        .await?;
+    }
+
+    result.bufferbloat = result
+        .loaded_latency_increase_ms()
+        .map(BufferbloatGrade::from_latency_increase);
 
     // Signal completion
     if let Some(cb) = progress_ref {
@@ -59,3 +179,174 @@ pub async fn run_speed_test(
 
     Ok(result)
 }
+
+/// HTTP client shared by every phase of a test: no compression, Nagle
+/// disabled, and bound to the configured source address or interface.
+///
+/// Use this when calling the individual `measure_*` functions directly.
+pub fn build_client(config: &TestConfig) -> Result<reqwest::Client> {
+    Ok(
+        binding::configure_client(reqwest::Client::builder(), config)?
+            .timeout(config.timeout)
+            .pool_max_idle_per_host(config.parallel_connections)
+            .tcp_nodelay(true)
+            .no_gzip()
+            .no_brotli()
+            .no_deflate()
+            .connect_timeout(Duration::from_secs(10))
+            .build()?,
+    )
+}
+
+/// Runs the test once per address family selected by `config.ip_version`:
+/// IPv4 then IPv6 for [`IpVersion::Both`](crate::IpVersion::Both), otherwise a single run.
+///
+/// In `Both` mode each run continues past failed phases, so a broken IPv6
+/// path is reported alongside the IPv4 results instead of discarding them.
+pub async fn run_speed_tests_with_cancel(
+    config: &TestConfig,
+    progress: Option<ProgressCallback>,
+    cancel: CancellationToken,
+) -> Result<Vec<SpeedTestResult>> {
+    let passes = config.ip_version.passes();
+    let dual_stack = passes.len() > 1;
+    let mut results = Vec::with_capacity(passes.len());
+
+    for ip_version in passes {
+        let pass_config = TestConfig {
+            ip_version,
+            continue_on_error: config.continue_on_error || dual_stack,
+            ..config.clone()
+        };
+        results.push(
+            run_speed_test_with_cancel(&pass_config, progress.clone(), cancel.clone()).await?,
+        );
+    }
+
+    Ok(results)
+}
+
+/// Runs one phase. With `continue_on_error` set, a failure is recorded on
+/// `result` and `None` is returned so the remaining phases still run;
+/// cancellation always ends the test.
+async fn run_phase<T>(
+    config: &TestConfig,
+    cancel: &CancellationToken,
+    result: &mut SpeedTestResult,
+    phase: TestPhase,
+    fut: impl Future<Output = Result<T>>,
+) -> Result<Option<T>> {
+    match until_cancelled(cancel, result, fut).await {
+        Ok(value) => Ok(Some(value)),
+        Err(e @ SpeedTestError::Cancelled(_)) => Err(e),
+        Err(e) if config.continue_on_error => {
+            result.errors.push(PhaseError {
+                phase,
+                error: e.to_string(),
+            });
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Awaits a phase unless `cancel` fires first, in which case the phase is
+/// dropped and the results gathered so far are returned inside the error.
+async fn until_cancelled<T>(
+    cancel: &CancellationToken,
+    partial: &SpeedTestResult,
+    phase: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    match cancel.run_until_cancelled(phase).await {
+        Some(result) => result,
+        None => Err(SpeedTestError::Cancelled(Box::new(partial.clone()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use crate::mock_http;
+
+    #[tokio::test]
+    async fn test_connection_probe_failure_does_not_abort_the_test() {
+        // The probe sends the first request: close it unanswered
+        let probed = AtomicBool::new(false);
+        let addr = mock_http::spawn(move |_| {
+            probed
+                .swap(true, Ordering::SeqCst)
+                .then(|| mock_http::response(0, b""))
+        })
+        .await;
+
+        let config = TestConfig {
+            latency_samples: 2,
+            latency_warmup: 0,
+            packet_loss_count: 1,
+            skip_download: true,
+            skip_upload: true,
+            udp_echo_addr: None,
+            ..TestConfig::for_server(&format!("http://{addr}"))
+        };
+        let result = run_speed_test(&config, None).await.unwrap();
+
+        assert!(result.connection.is_none());
+        assert!(result.latency.is_some());
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].phase, TestPhase::Connection);
+    }
+
+    #[tokio::test]
+    async fn test_single_stream_failure_is_recorded_apart_from_download() {
+        // The main run makes a warmup and two parallel requests; every
+        // download after that, i.e. the single-stream run, is cut short
+        let downloads = AtomicUsize::new(0);
+        let addr = mock_http::spawn(move |line| {
+            if line.contains("bytes=1000") && downloads.fetch_add(1, Ordering::SeqCst) >= 3 {
+                Some(mock_http::response(1000, &[0; 10]))
+            } else {
+                Some(mock_http::response(1000, &[0; 1000]))
+            }
+        })
+        .await;
+
+        let config = TestConfig {
+            parallel_connections: 2,
+            download_sizes: vec![1000],
+            compare_single_stream: true,
+            continue_on_error: true,
+            latency_samples: 2,
+            latency_warmup: 0,
+            packet_loss_count: 1,
+            skip_upload: true,
+            udp_echo_addr: None,
+            ..TestConfig::for_server(&format!("http://{addr}"))
+        };
+        let result = run_speed_test(&config, None).await.unwrap();
+
+        assert!(result.download.is_some());
+        assert!(result.single_stream_download.is_none());
+        assert!(result.phase_error(TestPhase::Download).is_none());
+        assert!(result
+            .phase_error(TestPhase::SingleStreamDownload)
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_before_start_returns_empty_partial() {
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let err = run_speed_test_with_cancel(&TestConfig::default(), None, cancel)
+            .await
+            .unwrap_err();
+        match err {
+            SpeedTestError::Cancelled(partial) => {
+                assert!(partial.connection.is_none());
+                assert!(partial.latency.is_none());
+            }
+            other => panic!("expected Cancelled, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinHandle;
+
+use crate::config::TestConfig;
+use crate::progress::{ProgressCallback, ProgressUpdate, TestPhase};
+use crate::results::ThroughputSample;
+
+/// How often the shared byte counter is sampled during a transfer.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Cumulative bytes observed at a point in a transfer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteSample {
+    /// Seconds since the transfer started
+    pub elapsed_secs: f64,
+    /// Total bytes transferred so far
+    pub bytes: u64,
+}
+
+/// Background task that samples a transfer's byte counter at a fixed interval
+/// and hands each sample to a callback (typically progress reporting).
+pub struct TransferMonitor {
+    samples: Arc<Mutex<Vec<ByteSample>>>,
+    handle: JoinHandle<()>,
+}
+
+impl TransferMonitor {
+    pub fn start<F>(total_bytes: Arc<AtomicU64>, start: Instant, on_sample: F) -> Self
+    where
+        F: Fn(ByteSample) + Send + 'static,
+    {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let task_samples = samples.clone();
+
+        let handle = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(SAMPLE_INTERVAL).await;
+                let sample = ByteSample {
+                    elapsed_secs: start.elapsed().as_secs_f64(),
+                    bytes: total_bytes.load(Ordering::Acquire),
+                };
+                if let Ok(mut s) = task_samples.lock() {
+                    s.push(sample);
+                }
+                on_sample(sample);
+            }
+        });
+
+        Self { samples, handle }
+    }
+
+    /// Stops sampling and returns every sample taken, ending with `last`.
+    pub async fn finish(mut self, last: ByteSample) -> Vec<ByteSample> {
+        self.handle.abort();
+        let _ = (&mut self.handle).await;
+        let mut samples = self
+            .samples
+            .lock()
+            .map(|mut s| std::mem::take(&mut *s))
+            .unwrap_or_default();
+        samples.push(last);
+        samples
+    }
+}
+
+impl Drop for TransferMonitor {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Builds a sample callback that reports progress for a throughput phase.
+/// Progress is time-based when the phase has a time budget, otherwise byte-based.
+pub fn progress_reporter(
+    progress: Option<&ProgressCallback>,
+    phase: TestPhase,
+    expected_bytes: f64,
+    test_duration: Option<Duration>,
+) -> impl Fn(ByteSample) + Send + 'static {
+    let cb = progress.cloned();
+    move |sample: ByteSample| {
+        let Some(ref cb) = cb else {
+            return;
+        };
+        if sample.elapsed_secs <= 0.0 {
+            return;
+        }
+        let speed_bps = (sample.bytes as f64 * 8.0) / sample.elapsed_secs;
+        let fraction = match test_duration {
+            Some(d) => sample.elapsed_secs / d.as_secs_f64(),
+            None => sample.bytes as f64 / expected_bytes,
+        };
+        cb(ProgressUpdate {
+            phase: phase.clone(),
+            speed_mbps: Some(speed_bps / 1_000_000.0),
+            progress: fraction.min(1.0),
+            latency_ms: None,
+        });
+    }
+}
+
+/// Seconds from the start of a transfer that are treated as TCP slow-start
+/// ramp-up and excluded from the steady-state window.
+pub fn ramp_up_secs(config: &TestConfig, elapsed_secs: f64) -> f64 {
+    match config.phase_duration() {
+        Some(duration) => config.ramp_up.min(duration / 2).as_secs_f64(),
+        None => elapsed_secs * config.steady_state_skip.clamp(0.0, 0.9),
+    }
+}
+
+/// Throughput in bits per second between the first sample at or after
+/// `ramp_up_secs` and the last sample. Returns `None` if the window is empty.
+pub fn steady_state_bps(samples: &[ByteSample], ramp_up_secs: f64) -> Option<f64> {
+    let first = samples.iter().find(|s| s.elapsed_secs >= ramp_up_secs)?;
+    let last = samples.last()?;
+    let window_secs = last.elapsed_secs - first.elapsed_secs;
+    if window_secs <= 0.0 {
+        return None;
+    }
+    let bytes = last.bytes.saturating_sub(first.bytes);
+    Some((bytes as f64 * 8.0) / window_secs)
+}
+
+/// Converts cumulative byte samples into a throughput time series,
+/// computing instantaneous speed between consecutive samples.
+pub fn throughput_samples(samples: &[ByteSample]) -> Vec<ThroughputSample> {
+    let mut prev = ByteSample {
+        elapsed_secs: 0.0,
+        bytes: 0,
+    };
+    samples
+        .iter()
+        .map(|s| {
+            let dt = s.elapsed_secs - prev.elapsed_secs;
+            let mbps = if dt > 0.0 {
+                (s.bytes.saturating_sub(prev.bytes) as f64 * 8.0) / dt / 1_000_000.0
+            } else {
+                0.0
+            };
+            prev = *s;
+            ThroughputSample {
+                elapsed_secs: s.elapsed_secs,
+                bytes: s.bytes,
+                mbps,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(elapsed_secs: f64, bytes: u64) -> ByteSample {
+        ByteSample {
+            elapsed_secs,
+            bytes,
+        }
+    }
+
+    #[test]
+    fn test_steady_state_skips_ramp_up() {
+        // Slow first second, then a steady 1 MB/s
+        let samples = [
+            sample(0.5, 10_000),
+            sample(1.0, 50_000),
+            sample(2.0, 1_050_000),
+            sample(3.0, 2_050_000),
+        ];
+        let bps = steady_state_bps(&samples, 1.0).unwrap();
+        assert!((bps - 8_000_000.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_steady_state_empty_window() {
+        let samples = [sample(0.5, 10_000), sample(1.0, 50_000)];
+        assert_eq!(steady_state_bps(&samples, 1.0), None);
+        assert_eq!(steady_state_bps(&samples, 5.0), None);
+        assert_eq!(steady_state_bps(&[], 0.0), None);
+    }
+
+    #[test]
+    fn test_throughput_samples_instantaneous_speed() {
+        let series = throughput_samples(&[sample(0.5, 62_500), sample(1.0, 250_000)]);
+        assert_eq!(series.len(), 2);
+        assert!((series[0].mbps - 1.0).abs() < 1e-9);
+        assert!((series[1].mbps - 3.0).abs() < 1e-9);
+        assert_eq!(series[1].bytes, 250_000);
+    }
+
+    #[test]
+    fn test_ramp_up_secs_fixed_size() {
+        let config = TestConfig {
+            steady_state_skip: 0.25,
+            ..TestConfig::default()
+        };
+        assert!((ramp_up_secs(&config, 4.0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ramp_up_secs_timed_is_capped() {
+        let config = TestConfig {
+            test_duration: Some(Duration::from_secs(2)),
+            ramp_up: Duration::from_secs(5),
+            ..TestConfig::default()
+        };
+        assert!((ramp_up_secs(&config, 2.0) - 1.0).abs() < 1e-9);
+    }
+}
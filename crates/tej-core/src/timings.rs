@@ -0,0 +1,198 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+use url::Host;
+
+use crate::binding;
+use crate::config::TestConfig;
+use crate::error::{Result, SpeedTestError};
+use crate::results::ConnectionTimings;
+
+/// Opens a fresh connection to the latency endpoint and times each stage
+/// separately: DNS lookup, TCP connect, TLS handshake and time to first byte.
+///
+/// This bypasses the shared reqwest client on purpose, since its pooled
+/// connections would hide the setup cost we are trying to measure.
+pub async fn measure_connection_timings(
+    client: &reqwest::Client,
+    config: &TestConfig,
+) -> Result<ConnectionTimings> {
+    let request = config.backend.latency_request(client, config).build()?;
+
+    tokio::time::timeout(config.timeout, probe(request.url(), config))
+        .await
+        .map_err(|_| SpeedTestError::Timeout(config.timeout.as_millis() as u64))?
+}
+
+async fn probe(url: &reqwest::Url, config: &TestConfig) -> Result<ConnectionTimings> {
+    let host = url
+        .host()
+        .ok_or_else(|| SpeedTestError::Other(format!("URL has no host: {url}")))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| SpeedTestError::Other(format!("URL has no port: {url}")))?;
+
+    // An address literal needs no lookup, so its DNS time stays zero
+    let usable = |addr: &SocketAddr| binding::allows(config, addr);
+    let (addr, dns) = match host {
+        Host::Domain(domain) => {
+            let start = Instant::now();
+            let addr = tokio::net::lookup_host((domain, port)).await?.find(usable);
+            (addr, start.elapsed())
+        }
+        Host::Ipv4(ip) => (Some(SocketAddr::from((ip, port))), Duration::ZERO),
+        Host::Ipv6(ip) => (Some(SocketAddr::from((ip, port))), Duration::ZERO),
+    };
+    let addr = addr
+        .filter(usable)
+        .ok_or_else(|| no_usable_address(config, &host.to_string()))?;
+
+    let start = Instant::now();
+    let stream = binding::connect_tcp(config, addr).await?;
+    stream.set_nodelay(true)?;
+    let tcp_connect = start.elapsed();
+
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    // Host displays IPv6 literals in brackets, as the header needs; port()
+    // is only set when it isn't the scheme's default
+    let host_header = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    };
+    let request = format!(
+        "GET {path} HTTP/1.1\r\nHost: {host_header}\r\nUser-Agent: tej\r\nConnection: close\r\n\r\n"
+    );
+
+    let (tls_handshake, ttfb) = if url.scheme() == "https" {
+        let server_name = match host {
+            Host::Domain(domain) => ServerName::try_from(domain.to_string())
+                .map_err(|e| SpeedTestError::Other(format!("Invalid TLS server name: {e}")))?,
+            Host::Ipv4(ip) => ServerName::IpAddress(IpAddr::V4(ip).into()),
+            Host::Ipv6(ip) => ServerName::IpAddress(IpAddr::V6(ip).into()),
+        };
+        let start = Instant::now();
+        let tls = tls_connector()?.connect(server_name, stream).await?;
+        let tls_handshake = start.elapsed();
+        (
+            Some(tls_handshake),
+            time_to_first_byte(tls, &request).await?,
+        )
+    } else {
+        (None, time_to_first_byte(stream, &request).await?)
+    };
+
+    Ok(ConnectionTimings::new(
+        addr.ip(),
+        dns,
+        tcp_connect,
+        tls_handshake,
+        ttfb,
+    ))
+}
+
+/// Sends `request` and waits for the first response byte.
+async fn time_to_first_byte<S>(mut stream: S, request: &str) -> Result<Duration>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let start = Instant::now();
+    stream.write_all(request.as_bytes()).await?;
+    stream.flush().await?;
+    let mut buf = [0u8; 1];
+    if stream.read(&mut buf).await? == 0 {
+        return Err(SpeedTestError::InvalidResponse(
+            "Connection closed before response".to_string(),
+        ));
+    }
+    Ok(start.elapsed())
+}
+
+/// Error for a host with no address matching the address family or source
+/// address restrictions.
+pub(crate) fn no_usable_address(config: &TestConfig, host: &str) -> SpeedTestError {
+    match config.source_address {
+        Some(source) => SpeedTestError::Other(format!(
+            "No addresses for {host} reachable from source address {source}"
+        )),
+        None => SpeedTestError::Other(format!(
+            "No {} addresses found for {host}",
+            config.ip_version
+        )),
+    }
+}
+
+fn tls_connector() -> Result<TlsConnector> {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| SpeedTestError::Other(format!("TLS configuration failed: {e}")))?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    /// Answers one request on `bind` and hands back the request text.
+    async fn serve_once(bind: &str) -> (SocketAddr, JoinHandle<String>) {
+        let listener = TcpListener::bind(bind).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let request = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let len = socket.read(&mut buf).await.unwrap_or(0);
+            let _ = socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await;
+            String::from_utf8_lossy(&buf[..len]).into_owned()
+        });
+        (addr, request)
+    }
+
+    #[tokio::test]
+    async fn test_timings_against_local_http_server() {
+        let (addr, _) = serve_once("127.0.0.1:0").await;
+        let config = TestConfig {
+            latency_url: format!("http://{addr}/__down"),
+            ..TestConfig::default()
+        };
+        let timings = measure_connection_timings(&reqwest::Client::new(), &config)
+            .await
+            .unwrap();
+        assert_eq!(timings.remote_ip, addr.ip());
+        assert_eq!(timings.tls_handshake_ms, None);
+        assert!(timings.total_ms >= timings.ttfb_ms);
+    }
+
+    #[tokio::test]
+    async fn test_timings_against_ipv6_literal() {
+        let (addr, request) = serve_once("[::1]:0").await;
+        let config = TestConfig {
+            latency_url: format!("http://{addr}/__down"),
+            ..TestConfig::default()
+        };
+        let timings = measure_connection_timings(&reqwest::Client::new(), &config)
+            .await
+            .unwrap();
+        assert_eq!(timings.remote_ip, addr.ip());
+        // No lookup for an address literal
+        assert_eq!(timings.dns_ms, 0.0);
+        let host = format!("Host: [::1]:{}\r\n", addr.port());
+        assert!(request.await.unwrap().contains(&host));
+    }
+}
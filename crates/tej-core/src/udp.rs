@@ -0,0 +1,220 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::binding;
+use crate::config::TestConfig;
+use crate::error::{Result, SpeedTestError};
+use crate::progress::{ProgressCallback, ProgressUpdate, TestPhase};
+use crate::results::PacketLossResult;
+use crate::timings::no_usable_address;
+
+/// Prefix of every probe datagram. Echo servers should only reflect
+/// datagrams that start with it.
+pub const PROBE_MAGIC: &[u8; 4] = b"TEJ1";
+
+/// Probe datagram size: magic, sequence number, send offset, padding.
+pub const PROBE_SIZE: usize = 64;
+
+/// Delay between consecutive probes.
+const PROBE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Sends `config.packet_loss_count` sequenced datagrams to the UDP echo
+/// endpoint and measures how many come back, out of order, or duplicated,
+/// along with each probe's round-trip time.
+///
+/// Unlike the HTTP-based figure, lost datagrams are never retransmitted, so
+/// this reflects real packet loss on the path.
+pub async fn measure_udp_loss(
+    config: &TestConfig,
+    progress: Option<&ProgressCallback>,
+) -> Result<PacketLossResult> {
+    let echo_addr = config
+        .udp_echo_addr
+        .as_deref()
+        .ok_or_else(|| SpeedTestError::Other("No UDP echo address configured".to_string()))?;
+    let remote = tokio::net::lookup_host(echo_addr)
+        .await?
+        .find(|addr| binding::allows(config, addr))
+        .ok_or_else(|| no_usable_address(config, echo_addr))?;
+    let socket = Arc::new(binding::bind_udp(config, remote).await?);
+    socket.connect(remote).await?;
+
+    let count = config.packet_loss_count as u32;
+    let start = Instant::now();
+    let arrivals = Arc::new(Mutex::new(Vec::new()));
+
+    let receiver = {
+        let socket = socket.clone();
+        let arrivals = arrivals.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; PROBE_SIZE];
+            loop {
+                // Errors such as ECONNREFUSED from an ICMP port-unreachable
+                // are reported once per datagram, so later replies still arrive
+                let Ok(len) = socket.recv(&mut buf).await else {
+                    continue;
+                };
+                if let Some((seq, sent_at)) = parse_probe(&buf[..len]) {
+                    let rtt = start.elapsed().saturating_sub(sent_at);
+                    if let Ok(mut a) = arrivals.lock() {
+                        a.push((seq, rtt.as_secs_f64() * 1000.0));
+                    }
+                }
+            }
+        })
+    };
+
+    // A probe that fails to send is never answered, so it counts as lost
+    let mut send_error = None;
+    let mut sent_any = false;
+    for seq in 0..count {
+        match socket.send(&encode_probe(seq, start.elapsed())).await {
+            Ok(_) => sent_any = true,
+            Err(e) => send_error = Some(e),
+        }
+
+        if let Some(cb) = progress {
+            cb(ProgressUpdate {
+                phase: TestPhase::UdpPacketLoss,
+                speed_mbps: None,
+                progress: (seq + 1) as f64 / count as f64,
+                latency_ms: None,
+            });
+        }
+
+        tokio::time::sleep(PROBE_INTERVAL).await;
+    }
+
+    // Nothing went out at all: that's a broken socket, not packet loss
+    if let Some(e) = send_error.filter(|_| !sent_any) {
+        receiver.abort();
+        return Err(e.into());
+    }
+
+    // Give the last probes time to come back before counting them lost
+    let deadline = Instant::now() + config.packet_loss_timeout;
+    while Instant::now() < deadline {
+        let received = arrivals.lock().map(|a| a.len()).unwrap_or(0);
+        if received >= count as usize {
+            break;
+        }
+        tokio::time::sleep(PROBE_INTERVAL).await;
+    }
+    receiver.abort();
+
+    let arrivals = arrivals.lock().map(|a| a.clone()).unwrap_or_default();
+    Ok(analyze_arrivals(count, &arrivals))
+}
+
+fn encode_probe(seq: u32, sent_at: Duration) -> [u8; PROBE_SIZE] {
+    let mut buf = [0u8; PROBE_SIZE];
+    buf[..4].copy_from_slice(PROBE_MAGIC);
+    buf[4..8].copy_from_slice(&seq.to_be_bytes());
+    buf[8..16].copy_from_slice(&(sent_at.as_micros() as u64).to_be_bytes());
+    buf
+}
+
+/// Returns the sequence number and send offset of a well-formed probe.
+fn parse_probe(buf: &[u8]) -> Option<(u32, Duration)> {
+    if buf.len() < 16 || &buf[..4] != PROBE_MAGIC {
+        return None;
+    }
+    let seq = u32::from_be_bytes(buf[4..8].try_into().ok()?);
+    let sent_at = u64::from_be_bytes(buf[8..16].try_into().ok()?);
+    Some((seq, Duration::from_micros(sent_at)))
+}
+
+/// Computes loss, reordering, duplication and per-probe RTT from the
+/// `(sequence number, RTT in ms)` pairs of received probes, in arrival order.
+pub fn analyze_arrivals(sent: u32, arrivals: &[(u32, f64)]) -> PacketLossResult {
+    let mut rtt_ms = vec![None; sent as usize];
+    let mut highest = None;
+    let mut out_of_order = 0u32;
+    let mut duplicates = 0u32;
+
+    for &(seq, rtt) in arrivals.iter().filter(|(seq, _)| *seq < sent) {
+        let slot = &mut rtt_ms[seq as usize];
+        if slot.is_some() {
+            duplicates += 1;
+            continue;
+        }
+        *slot = Some(rtt);
+        // A new probe arriving after a later one was already seen
+        match highest {
+            Some(h) if seq < h => out_of_order += 1,
+            _ => highest = Some(seq),
+        }
+    }
+
+    PacketLossResult::new(rtt_ms, out_of_order, duplicates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_roundtrip() {
+        let probe = encode_probe(42, Duration::from_millis(5));
+        assert_eq!(parse_probe(&probe), Some((42, Duration::from_millis(5))));
+        assert_eq!(parse_probe(b"not a probe at all"), None);
+    }
+
+    #[test]
+    fn test_analyze_no_loss() {
+        let r = analyze_arrivals(4, &[(0, 1.0), (1, 2.0), (2, 3.0), (3, 4.0)]);
+        assert_eq!(r.received, 4);
+        assert_eq!(r.loss_percent, 0.0);
+        assert_eq!(r.out_of_order, 0);
+        assert_eq!(r.avg_rtt_ms(), Some(2.5));
+    }
+
+    #[test]
+    fn test_analyze_loss_reorder_and_duplicates() {
+        // 1 lost, 2 arrives after 3, 0 duplicated
+        let r = analyze_arrivals(5, &[(0, 1.0), (0, 9.0), (3, 1.0), (2, 1.0), (4, 1.0)]);
+        assert_eq!(r.received, 4);
+        assert!((r.loss_percent - 20.0).abs() < 1e-9);
+        assert_eq!(r.out_of_order, 1);
+        assert_eq!(r.duplicates, 1);
+        // The first copy's RTT is kept and the lost probe has none
+        assert_eq!(
+            r.rtt_ms,
+            vec![Some(1.0), None, Some(1.0), Some(1.0), Some(1.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_probes_survive_an_unreachable_port() {
+        // Nothing listens at first, so the early probes draw ICMP
+        // port-unreachable errors before the echo server comes up
+        let addr = std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        tokio::spawn(async move {
+            tokio::time::sleep(PROBE_INTERVAL * 3).await;
+            let echo = tokio::net::UdpSocket::bind(addr).await.unwrap();
+            let mut buf = [0u8; PROBE_SIZE];
+            while let Ok((len, from)) = echo.recv_from(&mut buf).await {
+                let _ = echo.send_to(&buf[..len], from).await;
+            }
+        });
+
+        let config = TestConfig {
+            udp_echo_addr: Some(addr.to_string()),
+            packet_loss_count: 10,
+            packet_loss_timeout: Duration::from_secs(1),
+            ..TestConfig::default()
+        };
+        let result = measure_udp_loss(&config, None).await.unwrap();
+        assert_eq!(result.sent, 10);
+        assert!(result.received > 0);
+    }
+
+    #[test]
+    fn test_analyze_nothing_sent() {
+        let r = analyze_arrivals(0, &[]);
+        assert_eq!(r.loss_percent, 0.0);
+    }
+}
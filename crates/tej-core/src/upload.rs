@@ -3,13 +3,40 @@ use std::sync::Arc;
 use std::time::Instant;
 
 use bytes::Bytes;
+use reqwest::header::{HeaderValue, CONTENT_LENGTH};
+use reqwest::Body;
 use tokio::task::JoinSet;
 
+use crate::adaptive::ramp_connections;
+use crate::backend::clone_request;
 use crate::config::TestConfig;
 use crate::data::random_payload;
 use crate::error::Result;
+use crate::latency::LoadedLatencyProbe;
 use crate::progress::{ProgressCallback, ProgressUpdate, TestPhase};
-use crate::results::ThroughputResult;
+use crate::results::{ConnectionStats, ThroughputResult};
+use crate::sampler::{
+    progress_reporter, ramp_up_secs, steady_state_bps, throughput_samples, ByteSample,
+    TransferMonitor,
+};
+
+/// Size of the chunks an upload body is streamed in.
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Streams `data` as a request body, adding each chunk to the connection and
+/// phase byte counters as the HTTP client takes it for sending, so progress
+/// reflects bytes in flight rather than completed requests.
+fn counting_body(data: Bytes, conn: Arc<AtomicU64>, total: Arc<AtomicU64>) -> Body {
+    let chunks = (0..data.len())
+        .step_by(UPLOAD_CHUNK_SIZE)
+        .map(move |offset| {
+            let chunk = data.slice(offset..(offset + UPLOAD_CHUNK_SIZE).min(data.len()));
+            conn.fetch_add(chunk.len() as u64, Ordering::Release);
+            total.fetch_add(chunk.len() as u64, Ordering::Release);
+            Ok::<_, std::io::Error>(chunk)
+        });
+    Body::wrap_stream(futures::stream::iter(chunks))
+}
 
 pub async fn measure_upload(
     client: &reqwest::Client,
@@ -17,77 +44,144 @@ pub async fn measure_upload(
     progress: Option<&ProgressCallback>,
 ) -> Result<ThroughputResult> {
     let upload_size = config.upload_size;
+    let probe = LoadedLatencyProbe::start(client, config)?;
+    let request = config.backend.upload_request(client, config).build()?;
     let total_bytes = Arc::new(AtomicU64::new(0));
     let start = Instant::now();
-    let mut tasks = JoinSet::new();
+    let deadline = config.phase_duration().map(|d| start + d);
+    let mut tasks: JoinSet<(ConnectionStats, Result<()>)> = JoinSet::new();
 
     // Pre-generate payload once, share via zero-copy Bytes across connections
     let payload: Bytes = random_payload(upload_size).into();
 
-    for _ in 0..config.parallel_connections {
+    let spawn_connection = |tasks: &mut JoinSet<_>, index: usize| -> Result<()> {
         let client = client.clone();
-        let url = config.upload_url.clone();
+        let request = clone_request(&request)?;
         let total = total_bytes.clone();
         let data = payload.clone(); // Bytes::clone is O(1) ref-count bump
 
+        // Adaptive mode opens connections late; time each from its own start
+        let conn_start = Instant::now();
+
         tasks.spawn(async move {
-            let size = data.len() as u64;
-            client
-                .post(&url)
-                .header("Content-Type", "application/octet-stream")
-                .body(data)
-                .send()
-                .await?
-                .error_for_status()?;
-            total.fetch_add(size, Ordering::Release);
-            Ok::<(), crate::error::SpeedTestError>(())
+            let conn_bytes = Arc::new(AtomicU64::new(0));
+            let transfer = async {
+                // In timed mode keep re-issuing requests until the deadline cuts us off
+                loop {
+                    let mut req = clone_request(&request)?;
+                    req.headers_mut()
+                        .insert(CONTENT_LENGTH, HeaderValue::from(data.len()));
+                    *req.body_mut() = Some(counting_body(
+                        data.clone(),
+                        conn_bytes.clone(),
+                        total.clone(),
+                    ));
+                    client.execute(req).await?.error_for_status()?;
+                    if deadline.is_none() {
+                        break;
+                    }
+                }
+                Ok::<(), crate::error::SpeedTestError>(())
+            };
+            let outcome = match deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline.into(), transfer)
+                    .await
+                    .unwrap_or(Ok(())),
+                None => transfer.await,
+            };
+            // Bytes already sent count even if the connection failed
+            let mut stats = ConnectionStats::new(
+                index,
+                conn_bytes.load(Ordering::Acquire),
+                conn_start.elapsed().as_secs_f64(),
+            );
+            stats.error = outcome.as_ref().err().map(ToString::to_string);
+            (stats, outcome)
         });
-    }
+        Ok(())
+    };
 
-    // Progress reporting loop
-    let progress_total = total_bytes.clone();
+    // Sample the byte counter for progress reporting and steady-state throughput
     let expected_total = (upload_size * config.parallel_connections) as f64;
-    let progress_handle = if let Some(cb) = progress {
-        let cb = cb.clone();
-        Some(tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(std::time::Duration::from_millis(250)).await;
-                let bytes = progress_total.load(Ordering::Acquire);
-                let elapsed = start.elapsed().as_secs_f64();
-                if elapsed > 0.0 {
-                    let speed_bps = (bytes as f64 * 8.0) / elapsed;
-                    cb(ProgressUpdate {
-                        phase: TestPhase::Upload,
-                        speed_mbps: Some(speed_bps / 1_000_000.0),
-                        progress: (bytes as f64 / expected_total).min(1.0),
-                        latency_ms: None,
-                    });
-                }
+    let monitor = TransferMonitor::start(
+        total_bytes.clone(),
+        start,
+        progress_reporter(
+            progress,
+            TestPhase::Upload,
+            expected_total,
+            config.phase_duration(),
+        ),
+    );
+
+    let ramp = match deadline {
+        Some(deadline) if config.adaptive_connections => {
+            spawn_connection(&mut tasks, 0)?;
+            let ramp = ramp_connections(
+                config.parallel_connections,
+                &total_bytes,
+                start,
+                deadline,
+                |index| spawn_connection(&mut tasks, index),
+            )
+            .await?;
+            Some(ramp)
+        }
+        _ => {
+            for index in 0..config.parallel_connections {
+                spawn_connection(&mut tasks, index)?;
             }
-        }))
-    } else {
-        None
+            None
+        }
     };
 
+    // A failed connection keeps its partial bytes; the phase only fails
+    // when no connection succeeded
+    let mut connections = Vec::with_capacity(config.parallel_connections);
+    let mut first_error = None;
     while let Some(result) = tasks.join_next().await {
-        result.map_err(|e| crate::error::SpeedTestError::Other(e.to_string()))??;
+        let (stats, outcome) =
+            result.map_err(|e| crate::error::SpeedTestError::Other(e.to_string()))?;
+        if let Err(e) = outcome {
+            first_error.get_or_insert(e);
+        }
+        connections.push(stats);
     }
-
-    if let Some(handle) = progress_handle {
-        handle.abort();
+    if let Some(e) = first_error {
+        if connections.iter().all(|c| c.error.is_some()) {
+            return Err(e);
+        }
     }
 
     let elapsed = start.elapsed().as_secs_f64();
     let bytes = total_bytes.load(Ordering::Acquire);
+    let samples = monitor
+        .finish(ByteSample {
+            elapsed_secs: elapsed,
+            bytes,
+        })
+        .await;
+    let loaded_latency = probe.finish().await;
+
+    let ramp_up = ramp.map_or(ramp_up_secs(config, elapsed), |r| {
+        ramp_up_secs(config, elapsed).max(r.settled_secs)
+    });
+    let mut result = ThroughputResult::new(bytes, elapsed)
+        .with_steady_state(steady_state_bps(&samples, ramp_up))
+        .with_connections(connections);
+    result.saturating_connections = ramp.and_then(|r| r.saturating_connections);
+    result.loaded_latency = loaded_latency;
+    result.samples = throughput_samples(&samples);
 
+    // Final progress update
     if let Some(cb) = progress {
         cb(ProgressUpdate {
             phase: TestPhase::Upload,
-            speed_mbps: Some((bytes as f64 * 8.0) / elapsed / 1_000_000.0),
+            speed_mbps: Some(result.mbps),
             progress: 1.0,
             latency_ms: None,
         });
     }
 
-    Ok(ThroughputResult::new(bytes, elapsed))
+    Ok(result)
 }
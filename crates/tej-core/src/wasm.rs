@@ -8,7 +8,6 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{Request, RequestInit, RequestMode, Response};
 
-
 // Speed test result structure
 #[derive(serde::Serialize)]
 pub struct WasmSpeedTestResult {
@@ -63,7 +62,7 @@ async fn measure_latency_js() -> Result<(WasmLatencyResult, Option<String>), JsV
         let _ = JsFuture::from(window.fetch_with_request(&request)).await;
         let _ = js_sys::Date::now() - start;
     }
-    
+
     // Actual measurements
     for _ in 0..20 {
         let start = js_sys::Date::now();
@@ -71,7 +70,7 @@ async fn measure_latency_js() -> Result<(WasmLatencyResult, Option<String>), JsV
 
         let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
         let resp: Response = resp_value.dyn_into()?;
-        
+
         // Get server location from cf-ray header
         if server_location.is_none() {
             let headers = resp.headers();
@@ -83,7 +82,7 @@ async fn measure_latency_js() -> Result<(WasmLatencyResult, Option<String>), JsV
                 }
             }
         }
-        
+
         let end = js_sys::Date::now();
         latencies.push(end - start);
     }
@@ -98,7 +97,7 @@ async fn measure_latency_js() -> Result<(WasmLatencyResult, Option<String>), JsV
         .iter()
         .max_by(|a, b| a.partial_cmp(b).unwrap())
         .unwrap_or(&0.0);
-    
+
     // Calculate jitter (mean absolute difference between consecutive samples)
     let jitter = if latencies.len() > 1 {
         let diffs: Vec<f64> = latencies.windows(2).map(|w| (w[1] - w[0]).abs()).collect();
@@ -106,7 +105,7 @@ async fn measure_latency_js() -> Result<(WasmLatencyResult, Option<String>), JsV
     } else {
         0.0
     };
-    
+
     Ok((
         WasmLatencyResult {
             avg_ms: avg,
@@ -128,7 +127,7 @@ async fn measure_download_js(
 
     let start = js_sys::Date::now();
     let mut total_bytes: u64 = 0;
-    
+
     let request = Request::new_with_str(&url)?;
     let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
     let resp: Response = resp_value.dyn_into()?;
@@ -138,15 +137,15 @@ async fn measure_download_js(
         let reader = body
             .get_reader()
             .dyn_into::<web_sys::ReadableStreamDefaultReader>()?;
-        
+
         loop {
             let promise = reader.read();
             let chunk = JsFuture::from(promise).await?;
-            
+
             let done = js_sys::Reflect::get(&chunk, &"done".into())?
                 .as_bool()
                 .unwrap_or(true);
-            
+
             if done {
                 break;
             }
@@ -173,7 +172,7 @@ async fn measure_download_js(
             }
         }
     }
-    
+
     let end = js_sys::Date::now();
     let duration_secs = (end - start) / 1000.0;
     let mbps = (total_bytes as f64 * 8.0) / (duration_secs * 1_000_000.0);
@@ -207,7 +206,7 @@ async fn measure_upload_js(
     opts.set_body(&body_value);
 
     let request = Request::new_with_str_and_init("https://speed.cloudflare.com/__up", &opts)?;
-    
+
     let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
     let _: Response = resp_value.dyn_into()?;
 
@@ -248,7 +247,7 @@ async fn measure_packet_loss_js() -> Result<f64, JsValue> {
 
         let request =
             Request::new_with_str_and_init("https://speed.cloudflare.com/__down?bytes=0", &opts)?;
-        
+
         // Set timeout - keep closure alive
         let timeout_callback = Closure::once_into_js(move || {
             controller.abort();
@@ -260,7 +259,7 @@ async fn measure_packet_loss_js() -> Result<f64, JsValue> {
                 timeout_ms as i32,
             )
             .map_err(|_| JsValue::from_str("Failed to set timeout"))?;
-        
+
         let result = JsFuture::from(window.fetch_with_request(&request)).await;
         let _ = window.clear_timeout_with_handle(timeout_id);
 
@@ -293,7 +292,7 @@ pub async fn run_speed_test_wasm(js_callback: JsValue) -> Result<JsValue, JsValu
     }
 
     let (latency, server) = measure_latency_js().await?;
-    
+
     if let Some(cb) = &callback {
         let _ = cb.call4(
             &JsValue::NULL,
@@ -319,7 +318,7 @@ pub async fn run_speed_test_wasm(js_callback: JsValue) -> Result<JsValue, JsValu
     }
 
     let upload = measure_upload_js(&callback).await?;
-    
+
     // Phase 4: Packet Loss
     if let Some(cb) = &callback {
         let _ = cb.call4(
@@ -332,7 +331,7 @@ pub async fn run_speed_test_wasm(js_callback: JsValue) -> Result<JsValue, JsValu
     }
 
     let packet_loss = measure_packet_loss_js().await?;
-    
+
     // Complete
     if let Some(cb) = &callback {
         let _ = cb.call4(
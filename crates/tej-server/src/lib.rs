@@ -0,0 +1,216 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use serde::Deserialize;
+use tej_core::udp::PROBE_MAGIC;
+use tokio::net::{TcpListener, UdpSocket};
+
+/// Default cap on the payload a single download request may ask for (250MB).
+pub const DEFAULT_MAX_DOWNLOAD_BYTES: usize = 250_000_000;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Location name reported to clients in the `cf-ray` header
+    pub location: String,
+    /// Largest payload a single download request may ask for
+    pub max_download_bytes: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            location: "LOCAL".to_string(),
+            max_download_bytes: DEFAULT_MAX_DOWNLOAD_BYTES,
+        }
+    }
+}
+
+struct AppState {
+    config: ServerConfig,
+    /// Incompressible data cycled for every download response
+    buffer: Bytes,
+}
+
+/// Routes compatible with the Cloudflare speed test protocol used by
+/// `tej_core::CloudflareBackend`:
+///
+/// - `GET /__down?bytes=N` streams N bytes of incompressible data
+/// - `POST /__up` accepts and discards an upload
+/// - `GET /__latency` returns an empty response for RTT probes
+pub fn router(config: ServerConfig) -> Router {
+    let state = Arc::new(AppState {
+        config,
+        buffer: Bytes::from(tej_core::data::generate_random_buffer()),
+    });
+
+    Router::new()
+        .route("/__down", get(download))
+        .route("/__up", post(upload))
+        .route("/__latency", get(latency))
+        .with_state(state)
+}
+
+/// Serves the speed test endpoints on `listener` until the process exits.
+pub async fn serve(listener: TcpListener, config: ServerConfig) -> std::io::Result<()> {
+    axum::serve(listener, router(config)).await
+}
+
+/// Listens on `addr` and serves the speed test endpoints until the process
+/// exits, answering UDP probes on the same address when `udp_echo` is set.
+pub async fn run(addr: SocketAddr, config: ServerConfig, udp_echo: bool) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| with_context(e, format!("failed to listen on {addr}")))?;
+    let addr = listener.local_addr()?;
+    println!("Tej server listening on http://{addr}");
+
+    if udp_echo {
+        let socket = UdpSocket::bind(addr)
+            .await
+            .map_err(|e| with_context(e, format!("failed to listen for UDP on {addr}")))?;
+        println!("Answering UDP probes on {addr}");
+        tokio::spawn(async move {
+            if let Err(e) = serve_udp_echo(socket).await {
+                eprintln!("UDP echo stopped: {e}");
+            }
+        });
+    }
+
+    serve(listener, config).await
+}
+
+fn with_context(e: std::io::Error, context: String) -> std::io::Error {
+    std::io::Error::new(e.kind(), format!("{context}: {e}"))
+}
+
+/// Echoes tej UDP probes back to their sender so clients can measure
+/// datagram loss. Anything that isn't a probe is dropped, so the server
+/// can't be used to reflect arbitrary traffic.
+///
+/// Receive errors are logged and skipped, since some platforms report an
+/// ICMP port-unreachable from an earlier reply as one.
+pub async fn serve_udp_echo(socket: UdpSocket) -> std::io::Result<()> {
+    let mut buf = [0u8; tej_core::udp::PROBE_SIZE];
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) => {
+                eprintln!("UDP echo receive failed: {e}");
+                continue;
+            }
+        };
+        if len == buf.len() && buf.starts_with(PROBE_MAGIC) {
+            // A failed reply is just a lost probe from the client's view
+            let _ = socket.send_to(&buf[..len], peer).await;
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DownloadQuery {
+    #[serde(default)]
+    bytes: usize,
+}
+
+async fn download(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DownloadQuery>,
+) -> Response {
+    let size = query.bytes;
+    if size > state.config.max_download_bytes {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("bytes must be at most {}", state.config.max_download_bytes),
+        )
+            .into_response();
+    }
+
+    // Stream zero-copy slices of the shared buffer instead of allocating `size` bytes
+    let buffer = state.buffer.clone();
+    let chunk_size = buffer.len();
+    let chunks = stream::iter((0..size).step_by(chunk_size)).map(move |offset| {
+        let len = chunk_size.min(size - offset);
+        Ok::<_, Infallible>(buffer.slice(..len))
+    });
+
+    (
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (header::CONTENT_LENGTH, size.to_string()),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+            (header::HeaderName::from_static("cf-ray"), ray_id(&state)),
+        ],
+        Body::from_stream(chunks),
+    )
+        .into_response()
+}
+
+async fn upload(State(state): State<Arc<AppState>>, body: Body) -> Response {
+    // Drain the body chunk by chunk so uploads of any size use constant memory
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        if chunk.is_err() {
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    }
+
+    [(header::HeaderName::from_static("cf-ray"), ray_id(&state))].into_response()
+}
+
+async fn latency(State(state): State<Arc<AppState>>) -> Response {
+    [
+        (header::CACHE_CONTROL, "no-store".to_string()),
+        (header::HeaderName::from_static("cf-ray"), ray_id(&state)),
+    ]
+    .into_response()
+}
+
+/// Builds a `cf-ray`-style header value ("hex-LOCATION") so clients can
+/// read the server location the same way they do for Cloudflare.
+fn ray_id(state: &AppState) -> String {
+    format!("{:016x}-{}", rand::random::<u64>(), state.config.location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn spawn_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = ServerConfig {
+            location: "TEST".to_string(),
+            max_download_bytes: 1_000_000,
+        };
+        tokio::spawn(serve(listener, config));
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_download_returns_requested_size() {
+        let base_url = spawn_server().await;
+        let resp = reqwest::get(format!("{base_url}/__down?bytes=1500"))
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        assert_eq!(resp.bytes().await.unwrap().len(), 1500);
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_oversized_request() {
+        let base_url = spawn_server().await;
+        let resp = reqwest::get(format!("{base_url}/__down?bytes=2000000"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), StatusCode::BAD_REQUEST.as_u16());
+    }
+}
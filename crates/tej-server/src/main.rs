@@ -0,0 +1,42 @@
+use std::net::SocketAddr;
+
+use clap::Parser;
+use tej_server::{ServerConfig, DEFAULT_MAX_DOWNLOAD_BYTES};
+
+#[derive(Parser)]
+#[command(
+    name = "tej-server",
+    about = "Tej server - self-hosted endpoint for Tej speed tests"
+)]
+struct Args {
+    /// Address to listen on
+    #[arg(short, long, default_value = "0.0.0.0:8080")]
+    listen: SocketAddr,
+
+    /// Location name reported to clients (shown as "Server")
+    #[arg(long, default_value = "LOCAL")]
+    location: String,
+
+    /// Don't answer UDP packet loss probes on the listen address
+    #[arg(long)]
+    no_udp: bool,
+
+    /// Largest payload a single download request may ask for, in bytes
+    #[arg(long, default_value_t = DEFAULT_MAX_DOWNLOAD_BYTES)]
+    max_download_bytes: usize,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+
+    let config = ServerConfig {
+        location: args.location,
+        max_download_bytes: args.max_download_bytes,
+    };
+
+    if let Err(e) = tej_server::run(args.listen, config, !args.no_udp).await {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
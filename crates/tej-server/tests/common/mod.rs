@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use tej_core::TestConfig;
+use tej_server::{serve, serve_udp_echo, ServerConfig};
+use tokio::net::{TcpListener, UdpSocket};
+
+/// Starts a server with UDP echo on a free local port and returns its base URL.
+pub async fn spawn_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = ServerConfig {
+        location: "TEST".to_string(),
+        ..ServerConfig::default()
+    };
+    let udp = UdpSocket::bind(addr).await.unwrap();
+    tokio::spawn(serve(listener, config));
+    tokio::spawn(serve_udp_echo(udp));
+    format!("http://{addr}")
+}
+
+/// Config for a quick run against `base_url`: two connections, small
+/// transfers and a couple of latency and loss probes.
+pub fn test_config(base_url: &str) -> TestConfig {
+    TestConfig {
+        parallel_connections: 2,
+        download_sizes: vec![10_000, 20_000, 50_000, 100_000],
+        upload_size: 100_000,
+        latency_samples: 2,
+        latency_warmup: 0,
+        packet_loss_count: 1,
+        packet_loss_timeout: Duration::from_secs(1),
+        ..TestConfig::for_server(base_url)
+    }
+}
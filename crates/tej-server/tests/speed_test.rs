@@ -0,0 +1,156 @@
+//! Full client runs against a local server.
+
+mod common;
+
+use std::time::Duration;
+
+use common::{spawn_server, test_config};
+use tej_core::{
+    AddressFamily, CancellationToken, IpVersion, SpeedTestError, TestConfig, TestPhase,
+};
+
+#[tokio::test]
+async fn test_full_speed_test_against_local_server() {
+    let base_url = spawn_server().await;
+    let config = TestConfig {
+        packet_loss_count: 3,
+        ..test_config(&base_url)
+    };
+
+    let result = tej_core::run_speed_test(&config, None).await.unwrap();
+    assert_eq!(result.server_location, Some("TEST".to_string()));
+    assert!(result.connection.is_some());
+    assert_eq!(result.latency.unwrap().samples.len(), 2);
+    assert!(result.download.unwrap().bytes_transferred >= 2 * 20_000);
+    let upload = result.upload.unwrap();
+    assert_eq!(upload.bytes_transferred, 2 * 100_000);
+    assert_eq!(upload.connections.len(), 2);
+    assert!(upload.connections.iter().all(|c| c.bytes == 100_000));
+    assert!(upload.fairness.is_some());
+    assert_eq!(result.packet_loss.unwrap().loss_percent, 0.0);
+    let udp = result.udp_packet_loss.unwrap();
+    assert_eq!(udp.sent, 3);
+    assert_eq!(udp.received, 3);
+    assert!(udp.rtt_ms.iter().all(Option::is_some));
+    assert!(result.errors.is_empty());
+}
+
+#[tokio::test]
+async fn test_broken_upload_keeps_download_with_continue_on_error() {
+    let base_url = spawn_server().await;
+    let config = TestConfig {
+        upload_url: format!("{base_url}/missing"),
+        continue_on_error: true,
+        ..test_config(&base_url)
+    };
+
+    let result = tej_core::run_speed_test(&config, None).await.unwrap();
+    assert!(result.download.is_some());
+    assert!(result.upload.is_none());
+    assert!(result.phase_error(TestPhase::Upload).is_some());
+    assert!(result.packet_loss.is_some());
+}
+
+#[tokio::test]
+async fn test_udp_failure_is_reported_apart_from_http_loss() {
+    let base_url = spawn_server().await;
+    let config = TestConfig {
+        // An IPv6 echo address can't be reached over IPv4
+        udp_echo_addr: Some("[::1]:9".to_string()),
+        ip_version: IpVersion::V4,
+        skip_download: true,
+        skip_upload: true,
+        continue_on_error: true,
+        ..test_config(&base_url)
+    };
+
+    let result = tej_core::run_speed_test(&config, None).await.unwrap();
+    assert!(result.packet_loss.is_some());
+    assert!(result.phase_error(TestPhase::PacketLoss).is_none());
+    assert!(result.udp_packet_loss.is_none());
+    assert!(result.phase_error(TestPhase::UdpPacketLoss).is_some());
+}
+
+#[tokio::test]
+async fn test_dual_stack_reports_each_family() {
+    // The server only listens on IPv4, so the IPv6 pass must fail
+    let base_url = spawn_server().await;
+    let config = TestConfig {
+        udp_echo_addr: None,
+        ip_version: IpVersion::Both,
+        ..test_config(&base_url)
+    };
+
+    let results = tej_core::run_speed_tests_with_cancel(&config, None, CancellationToken::new())
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].address_family, Some(AddressFamily::Ipv4));
+    assert_eq!(results[0].remote_ip, Some("127.0.0.1".parse().unwrap()));
+    assert!(results[0].errors.is_empty());
+    assert_eq!(results[1].address_family, Some(AddressFamily::Ipv6));
+    assert!(results[1].latency.is_none());
+    assert!(results[1].phase_error(TestPhase::Connection).is_some());
+}
+
+#[tokio::test]
+async fn test_adaptive_download_ramps_connections() {
+    let base_url = spawn_server().await;
+    let config = TestConfig {
+        adaptive_connections: true,
+        parallel_connections: 4,
+        test_duration: Some(Duration::from_secs(3)),
+        skip_upload: true,
+        ..test_config(&base_url)
+    };
+
+    let result = tej_core::run_speed_test(&config, None).await.unwrap();
+    let download = result.download.unwrap();
+    // One step of ramping fits before the halfway cutoff
+    assert!(matches!(download.connections.len(), 1 | 2));
+    assert!(download.duration_secs >= 3.0);
+}
+
+#[tokio::test]
+async fn test_single_stream_comparison() {
+    let base_url = spawn_server().await;
+    let config = TestConfig {
+        parallel_connections: 3,
+        compare_single_stream: true,
+        ..test_config(&base_url)
+    };
+
+    let result = tej_core::run_speed_test(&config, None).await.unwrap();
+    assert_eq!(result.download.unwrap().connections.len(), 3);
+    assert_eq!(result.single_stream_download.unwrap().connections.len(), 1);
+    let single_upload = result.single_stream_upload.unwrap();
+    assert_eq!(single_upload.bytes_transferred, 100_000);
+}
+
+#[tokio::test]
+async fn test_cancel_mid_download_keeps_latency() {
+    let base_url = spawn_server().await;
+    let config = TestConfig {
+        test_duration: Some(Duration::from_secs(30)),
+        ..test_config(&base_url)
+    };
+    let cancel = CancellationToken::new();
+    let trigger = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        trigger.cancel();
+    });
+
+    let started = std::time::Instant::now();
+    let err = tej_core::run_speed_test_with_cancel(&config, None, cancel)
+        .await
+        .unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(10));
+    match err {
+        SpeedTestError::Cancelled(partial) => {
+            assert!(partial.latency.is_some());
+            assert!(partial.download.is_none());
+        }
+        other => panic!("expected Cancelled, got {other:?}"),
+    }
+}